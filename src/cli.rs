@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use crate::diff::Format;
+use clap::{Args, Parser, Subcommand};
 const DEFAULT_LIMIT: u32 = 100;
 #[derive(Debug, Parser)]
 #[command(name = "db-compare")]
@@ -8,6 +9,8 @@ pub struct Cli {
     pub command: Commands,
 }
 
+// Parsed once per process, boxing the `Run` arguments would not save anything.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand, Clone)]
 pub enum Commands {
     #[command(about = "Run two databases")]
@@ -16,6 +19,11 @@ pub enum Commands {
         db1: Option<String>,
         #[arg(long)]
         db2: Option<String>,
+        #[arg(
+            long = "targets",
+            help = "Comma separated databases also compared with `db1`, reported as DB3, DB4..."
+        )]
+        targets: Option<String>,
         #[arg(long, default_value_t = DEFAULT_LIMIT, help = "Queries limit, default: 100")]
         limit: u32,
         #[arg(
@@ -23,20 +31,134 @@ pub enum Commands {
             help = "Max rows to compare for `by_id` and `by_id_excluding_replica_updated_ats` job"
         )]
         by_id_sample_size: Option<u32>,
-        #[arg(long = "no-tls")]
+        #[arg(
+            long = "sample-strategy",
+            help = "Comma separated rows compared with `--by-id-sample-size`: `newest` (default), `random` or `spread` for every table, `table=strategy` for one table"
+        )]
+        sample_strategy: Option<String>,
+        #[arg(
+            long = "pool-size",
+            help = "Max open connections per database, default: 4 or `--concurrency` when greater"
+        )]
+        pool_size: Option<usize>,
+        #[arg(
+            long = "concurrency",
+            help = "Number of tables compared at the same time, default: 1"
+        )]
+        concurrency: Option<usize>,
+        #[arg(
+            long,
+            help = "How tables keyed by an integer id are walked: `windows` of `limit` ids (default) or `keyset`, windows of `limit` rows present in either database, for sparse ids. Affects jobs: `by_id` and `by_id_excluding_replica_updated_ats`"
+        )]
+        pagination: Option<String>,
+        #[arg(
+            long = "retries",
+            help = "Times a query failing with a connection or transient error is retried, default: 0"
+        )]
+        retries: Option<u32>,
+        #[arg(
+            long = "retry-backoff",
+            help = "Milliseconds to wait before the first retry, doubled after every retry, default: 1000"
+        )]
+        retry_backoff: Option<u64>,
+        #[arg(
+            long = "statement-timeout",
+            help = "Milliseconds after which the databases cancel a query, default: no timeout"
+        )]
+        statement_timeout: Option<u64>,
+        #[arg(
+            long = "continue-on-error",
+            help = "Record a window whose queries still fail after the retries as an error finding and compare the next one instead of aborting the run. Affects jobs: `by_id` and `by_id_excluding_replica_updated_ats`"
+        )]
+        continue_on_error: bool,
+        #[arg(
+            long = "no-tls",
+            help = "Connect without TLS, same as `sslmode` `disable`"
+        )]
         no_tls: bool,
+        #[command(flatten)]
+        tls: TlsArgs,
+        #[arg(
+            long,
+            help = "Compare checksums of id ranges and only fetch rows for ranges that differ. Affects jobs: `by_id` and `by_id_excluding_replica_updated_ats`"
+        )]
+        checksum: bool,
+        #[arg(
+            long,
+            help = "Read each database from one snapshot in `REPEATABLE READ READ ONLY` transactions, so the whole run sees a consistent view per side"
+        )]
+        snapshot: bool,
+        #[arg(
+            long,
+            help = "Continue an interrupted run from the `state.json` checkpoint in the output folder, skipping the tables it already compared. Affects jobs: `by_id` and `by_id_excluding_replica_updated_ats`"
+        )]
+        resume: bool,
+        #[arg(
+            long = "db1-snapshot",
+            help = "Snapshot id exported elsewhere, e.g. by `pg_export_snapshot()`, to read DB1 from. Implies `--snapshot`"
+        )]
+        db1_snapshot: Option<String>,
+        #[arg(
+            long = "db2-snapshot",
+            help = "Snapshot id exported elsewhere to read DB2 from. Implies `--snapshot`"
+        )]
+        db2_snapshot: Option<String>,
         #[arg(
             long = "output-folder",
             help = "Destination folder for diff files, default: `./diffs`"
         )]
         output_folder: Option<String>,
-        #[arg(long = "tables", help = "Comma separated list of tables to check")]
+        #[arg(
+            long,
+            value_enum,
+            help = "Output format for diff files: `text` (default), `jsonl` (one JSON record per finding) or `repair` (SQL script making DB2 match DB1)"
+        )]
+        format: Option<Format>,
+        #[arg(
+            long = "tables",
+            help = "Comma separated list of tables to check, `table` in every schema or `schema.table`, globs (`audit_*`) and regexes (`^tmp_`) are accepted"
+        )]
         tables: Option<String>,
+        #[arg(
+            long = "exclude-tables",
+            help = "Comma separated list of tables left out of every job, same patterns as `--tables`"
+        )]
+        exclude_tables: Option<String>,
+        #[arg(
+            long = "schemas",
+            help = "Comma separated list of schemas to check, default: every schema"
+        )]
+        schemas: Option<String>,
+        #[arg(
+            long = "exclude-schemas",
+            help = "Comma separated list of schemas left out of every job"
+        )]
+        exclude_schemas: Option<String>,
+        #[arg(
+            long = "ignore-columns",
+            help = "Comma separated list of columns left out of comparisons, `column` for every table, `table.column` or `schema.table.column`"
+        )]
+        ignore_columns: Option<String>,
+        #[arg(
+            long = "normalize",
+            help = "Comma separated `column=rule` normalizing values before comparing them, `table.column=rule` for one table. Rules: `timestamp[:digits]`, `trim`, `lowercase`, `numeric[:decimals]`, `json`"
+        )]
+        normalize: Option<String>,
+        #[arg(
+            long = "tolerance",
+            help = "Comma separated `column=tolerance` within which values are approximately equal, `table.column=` for one table, `type:name=` for columns of a type. Tolerances: `0.01` absolute, `0.1%` relative, `5ms`, `2s` or `1m` for timestamps"
+        )]
+        tolerance: Option<String>,
         #[arg(
             long = "jobs",
-            help = "Comma separated job list to run, default: `by_id_excluding_replica_updated_ats`, options: `counters, updated_ats, created_ats, by_id, by_id_excluding_replica_updated_ats`"
+            help = "Comma separated job list to run, default: `by_id_excluding_replica_updated_ats`, options: `counters, updated_ats, created_ats, by_id, by_id_excluding_replica_updated_ats, schema, constraints`"
         )]
         jobs: Option<String>,
+        #[arg(
+            long = "warning-jobs",
+            help = "Comma separated list of jobs whose differences do not make the run exit with a failure code, example: `counters`"
+        )]
+        warning_jobs: Option<String>,
         #[arg(long, short, help = "Yaml config file")]
         config: Option<String>,
         #[arg(
@@ -51,3 +173,37 @@ pub enum Commands {
         file: String,
     },
 }
+
+/// TLS options of each database, `sslmode` is one of `disable`, `prefer` (default),
+/// `require`, `verify-ca` or `verify-full`.
+#[derive(Debug, Args, Clone, Default)]
+pub struct TlsArgs {
+    #[arg(
+        long = "db1-sslmode",
+        help = "TLS mode for DB1: disable, prefer, require, verify-ca or verify-full, default: prefer, or the sslmode of the URL"
+    )]
+    pub db1_sslmode: Option<String>,
+    #[arg(
+        long = "db1-sslrootcert",
+        help = "CA bundle to verify DB1's certificate"
+    )]
+    pub db1_sslrootcert: Option<String>,
+    #[arg(long = "db1-sslcert", help = "Client certificate for DB1")]
+    pub db1_sslcert: Option<String>,
+    #[arg(long = "db1-sslkey", help = "Client certificate key for DB1")]
+    pub db1_sslkey: Option<String>,
+    #[arg(
+        long = "db2-sslmode",
+        help = "TLS mode for DB2: disable, prefer, require, verify-ca or verify-full, default: prefer, or the sslmode of the URL"
+    )]
+    pub db2_sslmode: Option<String>,
+    #[arg(
+        long = "db2-sslrootcert",
+        help = "CA bundle to verify DB2's certificate"
+    )]
+    pub db2_sslrootcert: Option<String>,
+    #[arg(long = "db2-sslcert", help = "Client certificate for DB2")]
+    pub db2_sslcert: Option<String>,
+    #[arg(long = "db2-sslkey", help = "Client certificate key for DB2")]
+    pub db2_sslkey: Option<String>,
+}
@@ -0,0 +1,305 @@
+use super::{column_of, JsonMap};
+use regex::Regex;
+use serde_json::{Number, Value};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Rewrites the values of a column read from either database before rows are
+/// compared, so representation differences do not show up as diffs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalizer {
+    /// Timestamps keep at most this many fractional second digits.
+    Timestamp(usize),
+    /// Strings without leading and trailing whitespace.
+    Trim,
+    Lowercase,
+    /// Numbers and numeric strings as decimals without leading or trailing
+    /// zeros, rounded to this many decimals when given, so `1.0` and `1` are
+    /// equal. Decimals with more digits than a float holds are kept as strings.
+    Numeric(Option<usize>),
+    /// JSON text parsed, so key order and spacing do not matter.
+    Json,
+}
+
+impl FromStr for Normalizer {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, digits) = match s.split_once(':') {
+            Some((name, digits)) => (name, Some(digits)),
+            None => (s, None),
+        };
+        let parse = |digits: &str| {
+            digits
+                .parse::<u8>()
+                .map_err(|_| anyhow::anyhow!("Invalid digits in normalizer: {}", s))
+        };
+        match (name, digits) {
+            ("timestamp", None) => Ok(Normalizer::Timestamp(0)),
+            ("timestamp", Some(d)) => Ok(Normalizer::Timestamp(usize::from(parse(d)?))),
+            ("trim", None) => Ok(Normalizer::Trim),
+            ("lowercase", None) => Ok(Normalizer::Lowercase),
+            ("numeric", None) => Ok(Normalizer::Numeric(None)),
+            ("numeric", Some(d)) => Ok(Normalizer::Numeric(Some(usize::from(parse(d)?)))),
+            ("json", None) => Ok(Normalizer::Json),
+            _ => Err(anyhow::anyhow!("Unknown normalizer: {}", s)),
+        }
+    }
+}
+
+impl Normalizer {
+    /// `value` normalized, values of another type are left unchanged.
+    pub fn apply(&self, value: Value) -> Value {
+        match (self, value) {
+            (Self::Timestamp(digits), Value::String(s)) => {
+                Value::String(truncate_timestamp(&s, *digits))
+            }
+            (Self::Trim, Value::String(s)) => Value::String(s.trim().to_string()),
+            (Self::Lowercase, Value::String(s)) => Value::String(s.to_lowercase()),
+            (Self::Numeric(digits), value) => {
+                let decimal = match &value {
+                    Value::Number(n) => decimal(&n.to_string(), *digits),
+                    Value::String(s) => decimal(s.trim(), *digits),
+                    _ => None,
+                };
+                let Some(decimal) = decimal else {
+                    return value;
+                };
+                // a float would round away the digits a difference may be in
+                match Number::from_str(&decimal) {
+                    Ok(n) if n.to_string() == decimal => Value::Number(n),
+                    _ => Value::String(decimal),
+                }
+            }
+            (Self::Json, Value::String(s)) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+            (_, value) => value,
+        }
+    }
+}
+
+/// Normalizes the columns of `row` that have a rule in `rules`.
+pub fn normalize(row: &mut JsonMap, rules: &[(String, Normalizer)]) {
+    for (column, normalizer) in rules {
+        if let Some(value) = row.get_mut(column) {
+            *value = normalizer.apply(value.take());
+        }
+    }
+}
+
+/// `(column, normalizer)` rules of `entries` applying to `table`.
+pub fn rules(entries: &[(String, Normalizer)], table: &str) -> Vec<(String, Normalizer)> {
+    entries
+        .iter()
+        .filter_map(|(entry, normalizer)| column_of(entry, table).map(|c| (c, *normalizer)))
+        .collect()
+}
+
+fn truncate_timestamp(s: &str, digits: usize) -> String {
+    static TIMESTAMP: OnceLock<Regex> = OnceLock::new();
+    let re = TIMESTAMP.get_or_init(|| {
+        Regex::new(r"^(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2})\.(\d+)(.*)$").unwrap()
+    });
+    let Some(captures) = re.captures(s) else {
+        return s.to_string();
+    };
+    let fraction = &captures[2][..captures[2].len().min(digits)];
+    // `.5` and `.500` are the same instant
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{}{}", &captures[1], &captures[3])
+    } else {
+        format!("{}.{fraction}{}", &captures[1], &captures[3])
+    }
+}
+
+/// Largest exponent expanded by `decimal`.
+const MAX_EXPONENT: i64 = 4096;
+
+/// `s`, a decimal number with an optional exponent, written without exponent,
+/// leading or trailing zeros and rounded half away from zero to `digits`
+/// decimals when given. `None` when `s` is not a number.
+fn decimal(s: &str, digits: Option<usize>) -> Option<String> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (mantissa, exponent) = match s.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if integer.len() + fraction.len() == 0 || !is_digits(integer) || !is_digits(fraction) {
+        return None;
+    }
+    // bounds the zeros written out for the exponent
+    if exponent.abs() > MAX_EXPONENT {
+        return None;
+    }
+    let mut all: Vec<u8> = integer.bytes().chain(fraction.bytes()).collect();
+    let mut point = integer.len() as i64 + exponent;
+    if point < 0 {
+        all.splice(
+            0..0,
+            std::iter::repeat_n(b'0', point.unsigned_abs() as usize),
+        );
+        point = 0;
+    }
+    let mut point = point as usize;
+    if point > all.len() {
+        all.resize(point, b'0');
+    }
+    if let Some(digits) = digits {
+        if all.len() > point + digits {
+            let round_up = all[point + digits] >= b'5';
+            all.truncate(point + digits);
+            if round_up {
+                increment(&mut all, &mut point);
+            }
+        }
+    }
+    let (integer, fraction) = all.split_at(point);
+    let integer = std::str::from_utf8(integer)
+        .unwrap()
+        .trim_start_matches('0');
+    let fraction = std::str::from_utf8(fraction).unwrap().trim_end_matches('0');
+    let integer = if integer.is_empty() { "0" } else { integer };
+    let sign = if negative && (integer != "0" || !fraction.is_empty()) {
+        "-"
+    } else {
+        ""
+    };
+    if fraction.is_empty() {
+        Some(format!("{sign}{integer}"))
+    } else {
+        Some(format!("{sign}{integer}.{fraction}"))
+    }
+}
+
+/// Adds one to the last of `digits`, moving `point` when a digit is carried
+/// in front of them.
+fn increment(digits: &mut Vec<u8>, point: &mut usize) {
+    for digit in digits.iter_mut().rev() {
+        if *digit == b'9' {
+            *digit = b'0';
+        } else {
+            *digit += 1;
+            return;
+        }
+    }
+    digits.insert(0, b'1');
+    *point += 1;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalizer_from_str() {
+        assert_eq!(
+            Normalizer::from_str("timestamp").unwrap(),
+            Normalizer::Timestamp(0)
+        );
+        assert_eq!(
+            Normalizer::from_str("timestamp:3").unwrap(),
+            Normalizer::Timestamp(3)
+        );
+        assert_eq!(
+            Normalizer::from_str("numeric:2").unwrap(),
+            Normalizer::Numeric(Some(2))
+        );
+        assert_eq!(Normalizer::from_str("json").unwrap(), Normalizer::Json);
+        assert!(Normalizer::from_str("trim:2").is_err());
+        assert!(Normalizer::from_str("timestamp:x").is_err());
+        assert!(Normalizer::from_str("uppercase").is_err());
+    }
+
+    #[test]
+    fn test_normalizer_apply() {
+        let timestamp = Normalizer::Timestamp(3);
+        assert_eq!(
+            timestamp.apply(json!("2020-05-07T20:52:24.123456")),
+            json!("2020-05-07T20:52:24.123")
+        );
+        assert_eq!(
+            timestamp.apply(json!("2020-05-07T20:52:24.1+00:00")),
+            json!("2020-05-07T20:52:24.1+00:00")
+        );
+        assert_eq!(
+            Normalizer::Timestamp(0).apply(json!("2020-05-07T20:52:24.9")),
+            json!("2020-05-07T20:52:24")
+        );
+        assert_eq!(Normalizer::Trim.apply(json!(" a b ")), json!("a b"));
+        assert_eq!(Normalizer::Lowercase.apply(json!("AbC")), json!("abc"));
+        assert_eq!(
+            Normalizer::Numeric(None).apply(json!(1)),
+            Normalizer::Numeric(None).apply(json!(1.0))
+        );
+        assert_eq!(
+            Normalizer::Numeric(Some(2)).apply(json!("1.004")),
+            Normalizer::Numeric(None).apply(json!(1))
+        );
+        assert_eq!(Normalizer::Numeric(None).apply(json!("n/a")), json!("n/a"));
+        assert_eq!(Normalizer::Numeric(None).apply(json!("NaN")), json!("NaN"));
+        assert_eq!(
+            Normalizer::Json.apply(json!("{\"b\": 1,  \"a\": [2]}")),
+            json!({"a": [2], "b": 1})
+        );
+        assert_eq!(Normalizer::Trim.apply(json!(null)), json!(null));
+    }
+
+    #[test]
+    fn test_normalizer_numeric_keeps_every_digit() {
+        let text = |value: Value| match value {
+            Value::String(s) => s,
+            value => value.to_string(),
+        };
+        let numeric = |value: Value| text(Normalizer::Numeric(None).apply(value));
+        assert_eq!(numeric(json!(9007199254740993_i64)), "9007199254740993");
+        assert_ne!(
+            numeric(json!(9007199254740993_i64)),
+            numeric(json!(9007199254740992_i64))
+        );
+        assert_eq!(
+            numeric(json!("12345678901234567890.123456789012345678900")),
+            "12345678901234567890.1234567890123456789"
+        );
+        assert_ne!(
+            numeric(json!("0.10000000000000000001")),
+            numeric(json!("0.1"))
+        );
+        assert_eq!(numeric(json!("-000.50")), "-0.5");
+        assert_eq!(numeric(json!("-0.0")), "0");
+        assert_eq!(numeric(json!("1.5e3")), "1500");
+        assert_eq!(numeric(json!("15E-4")), "0.0015");
+
+        let rounded = |digits: usize, value: &str| {
+            text(Normalizer::Numeric(Some(digits)).apply(json!(value)))
+        };
+        assert_eq!(rounded(2, "1.005"), "1.01");
+        assert_eq!(rounded(2, "-1.005"), "-1.01");
+        assert_eq!(rounded(2, "9.999"), "10");
+        assert_eq!(rounded(0, "0.4"), "0");
+        assert_eq!(rounded(2, "99999999999999999.994"), "99999999999999999.99");
+    }
+
+    #[test]
+    fn test_rules() {
+        let entries = vec![
+            ("name".to_string(), Normalizer::Trim),
+            ("users.name".to_string(), Normalizer::Lowercase),
+            ("events.at".to_string(), Normalizer::Timestamp(0)),
+        ];
+        assert_eq!(
+            rules(&entries, "public.users"),
+            vec![
+                ("name".to_string(), Normalizer::Trim),
+                ("name".to_string(), Normalizer::Lowercase),
+            ]
+        );
+        let mut row: JsonMap = serde_json::from_value(json!({"id": 1, "name": " Ann "})).unwrap();
+        normalize(&mut row, &rules(&entries, "public.users"));
+        assert_eq!(row["name"], json!("ann"));
+    }
+}
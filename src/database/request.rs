@@ -1,9 +1,14 @@
+use super::{split_identifiers, Key, Normalizer, Pool, PrimaryKey, TablePattern, Tls};
+use crate::diff::Format;
 use crate::Config;
+use std::collections::HashSet;
+use std::sync::Arc;
 
 #[derive(Clone, Debug, PartialEq, Default)]
 pub enum DB {
     Master(String),
-    Replica(String),
+    /// Target database `index` compared with the master, `0` being DB2.
+    Replica(usize, String),
     #[default]
     None,
 }
@@ -12,7 +17,7 @@ impl DB {
     pub fn name(&self) -> String {
         match self {
             Self::Master(_) => "DB1".to_string(),
-            Self::Replica(_) => "DB2".to_string(),
+            Self::Replica(index, _) => target_name(*index),
             _ => panic!("Unset Database"),
         }
     }
@@ -20,46 +25,113 @@ impl DB {
     pub fn url(&self) -> String {
         match self {
             Self::Master(url) => url.clone(),
-            Self::Replica(url) => url.clone(),
+            Self::Replica(_, url) => url.clone(),
             _ => panic!("Unset Database"),
         }
     }
 }
+/// Name of target database `index` in the output, `DB2` for the first one.
+pub fn target_name(index: usize) -> String {
+    format!("DB{}", index + 2)
+}
+
 #[derive(Clone, Debug)]
 pub struct Request {
     pub config: DBConfig,
     pub db: DB,
     pub table: Option<String>,
     pub column: Option<String>,
-    pub bounds: Option<(u32, u32)>,
+    pub bounds: Option<(i64, i64)>,
+    pub primary_key: Option<PrimaryKey>,
+    pub key_bounds: Option<(Option<Key>, Option<Key>)>,
+    /// Keys of rows left out of comparisons.
+    pub excluding_keys: Option<Arc<HashSet<String>>>,
     pub tm_cutoff: chrono::NaiveDateTime,
 }
 
+impl Request {
+    /// TLS settings of the database this request goes to.
+    pub fn tls(&self) -> &Tls {
+        match self.db {
+            DB::Replica(..) => &self.config.db2_tls,
+            _ => &self.config.db1_tls,
+        }
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct RequestBuilder {
     config: DBConfig,
     table: Option<String>,
     column: Option<String>,
-    bounds: Option<(u32, u32)>,
+    bounds: Option<(i64, i64)>,
+    primary_key: Option<PrimaryKey>,
+    key_bounds: Option<(Option<Key>, Option<Key>)>,
+    excluding_keys: Option<Arc<HashSet<String>>>,
     tm_cutoff: chrono::NaiveDateTime,
 }
 
 #[derive(Clone, Debug)]
 pub struct DBConfig {
     pub db1: String,
-    pub db2: String,
-    pub white_listed_tables: Option<Vec<String>>,
-    pub tls: bool,
+    /// `db2` followed by the other targets.
+    pub targets: Vec<String>,
+    pub white_listed_tables: Option<Vec<TablePattern>>,
+    pub exclude_tables: Vec<TablePattern>,
+    pub schemas: Option<Vec<String>>,
+    pub exclude_schemas: Vec<String>,
+    pub db1_tls: Tls,
+    pub db2_tls: Tls,
     pub limit: u32,
+    pub ignore_columns: Vec<String>,
+    pub normalize: Vec<(String, Normalizer)>,
+    /// Numbers an `i64` can not hold are read as strings, keeping every digit
+    /// for repair statements.
+    pub exact_numbers: bool,
+    pub pool: Arc<Pool>,
+}
+impl DBConfig {
+    /// Whether objects of `schema` are compared, following `schemas` and `exclude_schemas`.
+    pub fn includes_schema(&self, schema: &str) -> bool {
+        self.schemas
+            .as_ref()
+            .is_none_or(|schemas| schemas.iter().any(|s| s == schema))
+            && !self.exclude_schemas.iter().any(|s| s == schema)
+    }
+
+    /// Whether the schema qualified `table` is compared, following
+    /// `white_listed_tables`, `exclude_tables` and the schema lists.
+    pub fn includes_table(&self, table: &str) -> bool {
+        let names = split_identifiers(table);
+        let schema = if names.len() > 1 { &names[0] } else { "public" };
+        self.includes_schema(schema)
+            && self
+                .white_listed_tables
+                .as_ref()
+                .is_none_or(|tables| tables.iter().any(|pattern| pattern.matches(table)))
+            && !self
+                .exclude_tables
+                .iter()
+                .any(|pattern| pattern.matches(table))
+    }
 }
+
 impl Default for DBConfig {
     fn default() -> Self {
         Self {
             db1: "FAKE".to_string(),
-            db2: "FAKE".to_string(),
+            targets: vec!["FAKE".to_string()],
             white_listed_tables: None,
-            tls: true,
+            exclude_tables: Vec::new(),
+            schemas: None,
+            exclude_schemas: Vec::new(),
+            db1_tls: Tls::default(),
+            db2_tls: Tls::default(),
             limit: 100,
+            ignore_columns: Vec::new(),
+            normalize: Vec::new(),
+            exact_numbers: false,
+            pool: Arc::default(),
         }
     }
 }
@@ -69,10 +141,18 @@ impl RequestBuilder {
         RequestBuilder {
             config: DBConfig {
                 db1: config.db1.clone(),
-                db2: config.db2.clone(),
+                targets: config.target_urls(),
                 white_listed_tables: config.white_listed_tables.clone(),
-                tls: config.tls,
+                exclude_tables: config.exclude_tables.clone(),
+                schemas: config.schemas.clone(),
+                exclude_schemas: config.exclude_schemas.clone(),
+                db1_tls: config.db1_tls.clone(),
+                db2_tls: config.db2_tls.clone(),
                 limit: config.limit,
+                ignore_columns: config.ignore_columns.clone(),
+                normalize: config.normalize.clone(),
+                exact_numbers: config.format == Format::Repair,
+                pool: Arc::clone(&config.pool),
             },
             ..RequestBuilder::default()
         }
@@ -85,11 +165,26 @@ impl RequestBuilder {
         self.column = Some(column.into());
         self
     }
-    pub fn bounds(mut self, bounds: (u32, u32)) -> Self {
+    pub fn bounds(mut self, bounds: (i64, i64)) -> Self {
         self.bounds = Some(bounds);
         self
     }
 
+    pub fn primary_key(mut self, primary_key: &PrimaryKey) -> Self {
+        self.primary_key = Some(primary_key.clone());
+        self
+    }
+    /// Rows with keys `>= lower` and `< upper`, `None` leaves that side open.
+    pub fn key_bounds(mut self, lower: Option<Key>, upper: Option<Key>) -> Self {
+        self.key_bounds = Some((lower, upper));
+        self
+    }
+    /// Rows with these keys are skipped rather than compared.
+    pub fn excluding_keys(mut self, keys: &Option<Arc<HashSet<String>>>) -> Self {
+        self.excluding_keys = keys.clone();
+        self
+    }
+
     pub fn tm_cutoff(mut self, cutoff: chrono::NaiveDateTime) -> Self {
         self.tm_cutoff = cutoff;
         self
@@ -102,15 +197,24 @@ impl RequestBuilder {
             table: self.table.clone(),
             column: self.column.clone(),
             bounds: self.bounds,
+            primary_key: self.primary_key.clone(),
+            key_bounds: self.key_bounds.clone(),
+            excluding_keys: self.excluding_keys.clone(),
             tm_cutoff: self.tm_cutoff,
         }
     }
-    pub fn build_replica(&self) -> Request {
+    pub fn build_target(&self, index: usize) -> Request {
         Request {
-            db: DB::Replica(self.config.db2.clone()),
+            db: DB::Replica(index, self.config.targets[index].clone()),
             ..self.build_master()
         }
     }
+    /// Requests to every target, DB2 first.
+    pub fn build_targets(&self) -> Vec<Request> {
+        (0..self.config.targets.len())
+            .map(|index| self.build_target(index))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -122,18 +226,41 @@ mod test {
         cli::Commands::Run {
             db1: Some("db1".to_string()),
             db2: Some("db2".to_string()),
+            targets: None,
             limit: 1,
             no_tls: false,
+            tls: cli::TlsArgs::default(),
+            checksum: false,
+            pagination: None,
+            snapshot: false,
+            resume: false,
+            db1_snapshot: None,
+            db2_snapshot: None,
             by_id_sample_size: None,
+            sample_strategy: None,
             output_folder: None,
+            format: None,
+            ignore_columns: None,
+            normalize: None,
+            tolerance: None,
+            warning_jobs: None,
+            pool_size: None,
+            concurrency: None,
+            retries: None,
+            retry_backoff: None,
+            statement_timeout: None,
+            continue_on_error: false,
             tables: None,
+            exclude_tables: None,
+            schemas: None,
+            exclude_schemas: None,
             config: None,
             tm_cutoff: None,
             jobs: None,
         }
     }
     fn config() -> Config {
-        Config::new(&default_args())
+        Config::new(&default_args()).unwrap()
     }
 
     #[test]
@@ -157,11 +284,57 @@ mod test {
         assert_eq!(master.bounds, Some((1, 2)));
         // assert_eq!(master.until, Some(3));
 
-        let replica = builder.build_replica();
-        assert_eq!(replica.db, DB::Replica("db2".to_string()));
+        let replica = builder.build_target(0);
+        assert_eq!(replica.db, DB::Replica(0, "db2".to_string()));
+        assert_eq!(replica.db.name(), "DB2");
         assert_eq!(replica.column, Some("column".to_string()));
         assert_eq!(replica.table, Some("table".to_string()));
         assert_eq!(replica.bounds, Some((1, 2)));
         // assert_eq!(replica.until, Some(3));
     }
+
+    #[test]
+    fn test_build_targets() {
+        let mut config = config();
+        config.targets = vec!["db3".to_string(), "db4".to_string()];
+        let targets = RequestBuilder::new(&config).table("table").build_targets();
+
+        let dbs: Vec<(String, String)> =
+            targets.iter().map(|r| (r.db.name(), r.db.url())).collect();
+        assert_eq!(
+            dbs,
+            vec![
+                ("DB2".to_string(), "db2".to_string()),
+                ("DB3".to_string(), "db3".to_string()),
+                ("DB4".to_string(), "db4".to_string()),
+            ]
+        );
+        assert!(targets.iter().all(|r| r.table == Some("table".to_string())));
+    }
+
+    #[test]
+    fn test_includes_table() {
+        let mut config = DBConfig {
+            white_listed_tables: Some(vec![
+                "users".parse().unwrap(),
+                "audit.events".parse().unwrap(),
+            ]),
+            ..DBConfig::default()
+        };
+        assert!(config.includes_table("public.users"));
+        assert!(config.includes_table("billing.users"));
+        assert!(config.includes_table("audit.events"));
+        assert!(!config.includes_table("public.events"));
+
+        config.exclude_tables = vec!["billing.*".parse().unwrap()];
+        assert!(config.includes_table("public.users"));
+        assert!(!config.includes_table("billing.users"));
+        config.exclude_tables = Vec::new();
+
+        config.schemas = Some(vec!["billing".to_string(), "audit".to_string()]);
+        config.exclude_schemas = vec!["audit".to_string()];
+        assert!(!config.includes_table("public.users"));
+        assert!(config.includes_table("billing.users"));
+        assert!(!config.includes_table("audit.events"));
+    }
 }
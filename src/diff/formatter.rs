@@ -1,29 +1,33 @@
 use crate::Config;
 
-use crate::database::{DBResultType, DBsResults, JsonMap};
+use crate::database::{key_of, key_to_s, DBResultType, DBsResults, JsonMap};
+use crate::diff::Tolerance;
+use serde_json::Value;
 use similar::{ChangeTag, TextDiff};
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 pub type FmtOutput = (
     Option<String>,
     Vec<String>,
     Option<Vec<String>>,
     Option<Vec<String>>,
 );
-pub fn call(config: &Config, result: DBsResults) -> Vec<FmtOutput> {
+pub fn call(config: &Config, key: &[String], result: DBsResults) -> Vec<FmtOutput> {
     let (header, a, b) = result;
-    let (rows, missing, extra) = generate_diff(config, &a, &b);
+    let (rows, missing, extra) = generate_diff(config, key, &a, &b);
 
     vec![(Some(header), rows, missing, extra)]
 }
 
 fn generate_diff(
     config: &Config,
+    key: &[String],
     a: &DBResultType,
     b: &DBResultType,
 ) -> (Vec<String>, Option<Vec<String>>, Option<Vec<String>>) {
     let (rows, missing, extra) = match (a, b) {
         (DBResultType::JsonMaps(_a), DBResultType::JsonMaps(_b)) => {
-            normalize_map_type(config, a, b)
+            normalize_map_type(config, key, a, b)
         }
 
         _ => {
@@ -39,6 +43,7 @@ fn generate_diff(
 
 fn normalize_map_type(
     config: &Config,
+    key: &[String],
     a: &DBResultType,
     b: &DBResultType,
 ) -> (Vec<String>, Option<Vec<String>>, Option<Vec<String>>) {
@@ -46,39 +51,195 @@ fn normalize_map_type(
         matches: (result_a, result_b),
         missing,
         extra,
-    } = only_matching_ids(a, b);
-
-    let rows = match result_a {
-        DBResultType::JsonMaps(a) => a
-            .into_iter()
-            .zip(result_b.to_h())
-            .map(|(a, b)| {
-                print_diff(produce_diff(
-                    config,
-                    &serde_json::to_string(&a).unwrap(),
-                    &serde_json::to_string(&b).unwrap(),
-                ))
-            })
-            .collect(),
-        DBResultType::GroupedRows(a) => a
-            .into_iter()
-            // TODO: remove duplication
-            .map(|(a, b)| {
-                print_diff(produce_diff(
-                    config,
-                    &serde_json::to_string(&a).unwrap(),
-                    &serde_json::to_string(&b).unwrap(),
-                ))
-            })
-            .collect(),
+    } = only_matching_keys(key, a, b);
+
+    let pairs: Vec<(JsonMap, JsonMap)> = match result_a {
+        DBResultType::JsonMaps(a) => a.into_iter().zip(result_b.to_h()).collect(),
+        DBResultType::GroupedRows(a) => a,
         _ => panic!("unexpected type: {:?}", result_a),
     };
+    let rows = pairs
+        .into_iter()
+        .map(|(a, b)| {
+            let row = print_diff(produce_diff(
+                config,
+                &serde_json::to_string(&a).unwrap(),
+                &serde_json::to_string(&b).unwrap(),
+            ));
+            if row.is_empty() {
+                return row;
+            }
+            changed_columns(&a, &b)
+                .into_iter()
+                .fold(row, |acc, (column, old, new)| {
+                    format!("{acc}\n{}", format_changed_column(&column, &old, &new))
+                })
+        })
+        .collect();
 
     let missing = do_unmached_rows_format(&missing, "-");
     let extra = do_unmached_rows_format(&extra, "+");
     (rows, missing, extra)
 }
 
+/// Differences in a formatted output: the changed, missing and extra rows.
+pub fn count((_header, rows, missing, extra): &FmtOutput) -> usize {
+    rows.iter().filter(|row| !row.is_empty()).count()
+        + missing.as_ref().map_or(0, Vec::len)
+        + extra.as_ref().map_or(0, Vec::len)
+}
+
+/// Structured differences of `result`, one JSON object per missing, extra or changed row.
+pub fn findings(key: &[String], result: &DBsResults) -> Vec<JsonMap> {
+    let (_header, a, b) = result;
+    let mut acc = Vec::new();
+    match (a, b) {
+        (DBResultType::JsonMaps(_), DBResultType::JsonMaps(_)) => {
+            let RowSelector {
+                matches: (result_a, result_b),
+                missing,
+                extra,
+            } = only_matching_keys(key, a, b);
+            let pairs = match result_a {
+                DBResultType::JsonMaps(a) => a.into_iter().zip(result_b.to_h()).collect(),
+                DBResultType::GroupedRows(a) => a,
+                _ => panic!("unexpected type: {:?}", result_a),
+            };
+            for (row_a, row_b) in pairs {
+                let columns = changed_columns(&row_a, &row_b);
+                if !columns.is_empty() {
+                    acc.push(finding("changed", key, &row_a, columns));
+                }
+            }
+            for row in missing.to_h() {
+                let columns = changed_columns(&row, &JsonMap::new());
+                acc.push(finding("missing", key, &row, columns));
+            }
+            for row in extra.to_h() {
+                let columns = changed_columns(&JsonMap::new(), &row);
+                acc.push(finding("extra", key, &row, columns));
+            }
+        }
+        _ => {
+            let (old, new) = (normalize_input(a).unwrap(), normalize_input(b).unwrap());
+            if old != new {
+                let mut data = JsonMap::new();
+                data.insert("kind".to_string(), Value::from("changed"));
+                data.insert("old".to_string(), Value::from(old));
+                data.insert("new".to_string(), Value::from(new));
+                acc.push(data);
+            }
+        }
+    }
+    acc
+}
+
+/// `~ column: old => new` line listing a changed column below its `>` row.
+pub fn format_changed_column(column: &str, old: &Value, new: &Value) -> String {
+    format!("~ {column}: {old} => {new}")
+}
+
+/// Columns whose values differ between `a` and `b` as `(column, old, new)`,
+/// a column missing on one side is reported as `null`.
+pub fn changed_columns(a: &JsonMap, b: &JsonMap) -> Vec<(String, Value, Value)> {
+    let mut columns: Vec<&String> = a.keys().chain(b.keys()).collect();
+    columns.sort();
+    columns.dedup();
+    columns
+        .into_iter()
+        .filter_map(|column| {
+            let old = a.get(column).cloned().unwrap_or_default();
+            let new = b.get(column).cloned().unwrap_or_default();
+            let missing = !a.contains_key(column) || !b.contains_key(column);
+            if old != new || missing {
+                Some((column.clone(), old, new))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn finding(
+    kind: &str,
+    key: &[String],
+    row: &JsonMap,
+    columns: Vec<(String, Value, Value)>,
+) -> JsonMap {
+    let mut data = JsonMap::new();
+    data.insert("kind".to_string(), Value::from(kind));
+    let primary_key: JsonMap = key
+        .iter()
+        .map(|column| (column.clone(), row.get(column).cloned().unwrap_or_default()))
+        .collect();
+    data.insert("key".to_string(), Value::Object(primary_key));
+    let columns: JsonMap = columns
+        .into_iter()
+        .map(|(column, old, new)| {
+            let mut change = JsonMap::new();
+            change.insert("old".to_string(), old);
+            change.insert("new".to_string(), new);
+            (column, Value::Object(change))
+        })
+        .collect();
+    data.insert("columns".to_string(), Value::Object(columns));
+    data
+}
+
+/// `result` without the pairs of rows, matched by `key`, whose changed columns
+/// are all within their `tolerances`, and the number of pairs left out.
+pub fn approximately_equal(
+    key: &[String],
+    result: DBsResults,
+    tolerances: &HashMap<String, Tolerance>,
+) -> (DBsResults, usize) {
+    let (header, a, b) = result;
+    let (rows_a, rows_b) = match (a, b) {
+        (DBResultType::JsonMaps(a), DBResultType::JsonMaps(b)) if !key.is_empty() => (a, b),
+        (a, b) => return ((header, a, b), 0),
+    };
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (position, row) in rows_b.iter().enumerate() {
+        positions
+            .entry(key_to_s(&key_of(key, row)))
+            .or_insert(position);
+    }
+    let mut equal = HashSet::new();
+    let rows_a: Vec<JsonMap> = rows_a
+        .into_iter()
+        .filter(|row| {
+            let Some(&position) = positions.get(&key_to_s(&key_of(key, row))) else {
+                return true;
+            };
+            let changed = changed_columns(row, &rows_b[position]);
+            let within = !changed.is_empty()
+                && changed.iter().all(|(column, old, new)| {
+                    tolerances
+                        .get(column)
+                        .is_some_and(|tolerance| tolerance.within(old, new))
+                });
+            if within && equal.insert(position) {
+                return false;
+            }
+            true
+        })
+        .collect();
+    let rows_b = rows_b
+        .into_iter()
+        .enumerate()
+        .filter(|(position, _)| !equal.contains(position))
+        .map(|(_, row)| row)
+        .collect();
+    (
+        (
+            header,
+            DBResultType::JsonMaps(rows_a),
+            DBResultType::JsonMaps(rows_b),
+        ),
+        equal.len(),
+    )
+}
+
 fn do_unmached_rows_format(missing: &DBResultType, operator: &str) -> Option<Vec<String>> {
     match missing {
         DBResultType::JsonMaps(s) => {
@@ -107,11 +268,15 @@ struct RowSelector {
     missing: DBResultType,
     extra: DBResultType,
 }
-fn only_matching_ids(a: &DBResultType, b: &DBResultType) -> RowSelector {
-    if let (DBResultType::JsonMaps(tmp_a), DBResultType::JsonMaps(_tmp_b)) = (a, b) {
-        if let Some(first) = tmp_a.clone().first() {
-            if first.contains_key("id") && first.get("id").unwrap().as_u64().is_some() {
-                return group_by_id(a, b);
+fn only_matching_keys(key: &[String], a: &DBResultType, b: &DBResultType) -> RowSelector {
+    if let (DBResultType::JsonMaps(tmp_a), DBResultType::JsonMaps(tmp_b)) = (a, b) {
+        if let Some(first) = tmp_a.first().or(tmp_b.first()) {
+            if !key.is_empty()
+                && key
+                    .iter()
+                    .all(|c| first.get(c).is_some_and(|v| !v.is_null()))
+            {
+                return group_by_key(key, a, b);
             }
         }
     };
@@ -122,17 +287,15 @@ fn only_matching_ids(a: &DBResultType, b: &DBResultType) -> RowSelector {
     }
 }
 
-fn group_by_id(a: &DBResultType, b: &DBResultType) -> RowSelector {
-    let mut btree: BTreeMap<u64, JsonMap> =
-        b.to_h().into_iter().fold(BTreeMap::new(), |mut acc, data| {
-            acc.insert(data.get("id").unwrap().as_u64().unwrap(), data);
+fn group_by_key(key: &[String], a: &DBResultType, b: &DBResultType) -> RowSelector {
+    let mut others: HashMap<String, JsonMap> =
+        b.to_h().into_iter().fold(HashMap::new(), |mut acc, data| {
+            acc.insert(key_to_s(&key_of(key, &data)), data);
             acc
         });
     let mut missing: Vec<JsonMap> = Vec::new();
     let acc: Vec<(JsonMap, JsonMap)> = a.to_h().into_iter().fold(Vec::new(), |mut acc, data| {
-        let id = data.get("id").unwrap().as_u64().unwrap();
-
-        if let Some(value) = btree.remove(&id) {
+        if let Some(value) = others.remove(&key_to_s(&key_of(key, &data))) {
             acc.push((data, value));
         } else {
             missing.push(data);
@@ -140,7 +303,8 @@ fn group_by_id(a: &DBResultType, b: &DBResultType) -> RowSelector {
         acc
     });
 
-    let extra = btree.into_values().collect::<Vec<JsonMap>>();
+    let mut extra = others.into_values().collect::<Vec<JsonMap>>();
+    extra.sort_by(|x, y| compare_keys(&key_of(key, x), &key_of(key, y)));
 
     RowSelector {
         matches: (DBResultType::GroupedRows(acc), DBResultType::Empty),
@@ -156,6 +320,24 @@ fn group_by_id(a: &DBResultType, b: &DBResultType) -> RowSelector {
         },
     }
 }
+
+/// Orders keys numerically for numbers and lexicographically for anything else.
+fn compare_keys(a: &[Value], b: &[Value]) -> Ordering {
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ordering = match (x, y) {
+            (Value::Number(x), Value::Number(y)) => x
+                .as_f64()
+                .unwrap_or_default()
+                .total_cmp(&y.as_f64().unwrap_or_default()),
+            (Value::String(x), Value::String(y)) => x.cmp(y),
+            (x, y) => x.to_string().cmp(&y.to_string()),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
 fn normalize_input(list: &DBResultType) -> Result<String, serde_json::Error> {
     let list: String = match list {
         DBResultType::Strings(l) => {
@@ -238,7 +420,7 @@ mod test {
     use serde_json::{from_str, Map, Value};
 
     #[test]
-    fn test_only_matching_ids() {
+    fn test_only_matching_keys() {
         let a = gen_data(1);
         let b = gen_data(2);
         let c = gen_data(3);
@@ -248,7 +430,8 @@ mod test {
             matches: (result_a, result_b),
             missing,
             extra: _,
-        } = only_matching_ids(
+        } = only_matching_keys(
+            &["id".to_string()],
             &DBResultType::JsonMaps(vec![a.clone(), b.clone(), c.clone()]),
             &DBResultType::JsonMaps(vec![a.clone(), b, d]),
         );
@@ -257,6 +440,100 @@ mod test {
         assert_eq!(missing.to_h(), vec![c]);
     }
 
+    #[test]
+    fn test_only_matching_composite_keys() {
+        let key = vec!["name".to_string(), "id".to_string()];
+        let a = gen_data(1);
+        let b = gen_data(2);
+        let mut renamed = gen_data(2);
+        renamed.insert("name".to_string(), Value::from("Jane_2"));
+        let c = gen_data(10);
+
+        let RowSelector {
+            matches: (result_a, _),
+            missing,
+            extra,
+        } = only_matching_keys(
+            &key,
+            &DBResultType::JsonMaps(vec![a.clone(), b.clone()]),
+            &DBResultType::JsonMaps(vec![c.clone(), renamed.clone(), a.clone()]),
+        );
+        assert_eq!(result_a.to_gr(), vec![(a.clone(), a)]);
+        assert_eq!(missing.to_h(), vec![b]);
+        assert_eq!(extra.to_h(), vec![renamed, c]);
+    }
+
+    #[test]
+    fn test_findings() {
+        let a = gen_data(1);
+        let mut changed = gen_data(1);
+        changed.insert("name".to_string(), Value::from("Jane_1"));
+        let b = gen_data(2);
+        let c = gen_data(3);
+        let result = (
+            "header".to_string(),
+            DBResultType::JsonMaps(vec![a, b]),
+            DBResultType::JsonMaps(vec![changed, c]),
+        );
+
+        let lines: Vec<String> = findings(&["id".to_string()], &result)
+            .iter()
+            .map(|f| serde_json::to_string(f).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"columns":{"name":{"new":"Jane_1","old":"John_1"}},"key":{"id":1},"kind":"changed"}"#,
+                r#"{"columns":{"id":{"new":null,"old":2},"name":{"new":null,"old":"John_2"}},"key":{"id":2},"kind":"missing"}"#,
+                r#"{"columns":{"id":{"new":3,"old":null},"name":{"new":"John_3","old":null}},"key":{"id":3},"kind":"extra"}"#,
+            ]
+        );
+
+        let result = (
+            "`users` count".to_string(),
+            DBResultType::Strings(vec!["41".to_string()]),
+            DBResultType::Strings(vec!["36".to_string()]),
+        );
+        let lines = findings(&[], &result);
+        assert_eq!(
+            serde_json::to_string(&lines[0]).unwrap(),
+            r#"{"kind":"changed","new":"36","old":"41"}"#
+        );
+    }
+
+    #[test]
+    fn test_approximately_equal() {
+        let mut a1 = gen_data(1);
+        a1.insert("price".to_string(), Value::from(1.0));
+        let mut b1 = a1.clone();
+        b1.insert("price".to_string(), Value::from(1.004));
+        let mut a2 = gen_data(2);
+        a2.insert("price".to_string(), Value::from(2.0));
+        let mut b2 = a2.clone();
+        b2.insert("price".to_string(), Value::from(2.5));
+        let mut a3 = gen_data(3);
+        a3.insert("price".to_string(), Value::from(3.0));
+        let mut b3 = a3.clone();
+        b3.insert("price".to_string(), Value::from(3.001));
+        b3.insert("name".to_string(), Value::from("Jane_3"));
+        let result = (
+            "header".to_string(),
+            DBResultType::JsonMaps(vec![a1, a2.clone(), a3.clone()]),
+            DBResultType::JsonMaps(vec![b1, b2.clone(), b3.clone()]),
+        );
+        let tolerances = HashMap::from([("price".to_string(), Tolerance::Absolute(0.01))]);
+
+        let ((_, a, b), equal) = approximately_equal(&["id".to_string()], result, &tolerances);
+        assert_eq!(equal, 1);
+        match (a, b) {
+            (DBResultType::JsonMaps(a), DBResultType::JsonMaps(b)) => {
+                assert_eq!(a, vec![a2, a3]);
+                assert_eq!(b, vec![b2, b3]);
+            }
+            _ => panic!("expected rows"),
+        }
+    }
+
     fn gen_data(id: u64) -> Map<String, Value> {
         let data = format!(r#"{{"id": {id},"name": "John_{id}"}}"#);
         let mut v: Value = from_str(&data).unwrap();
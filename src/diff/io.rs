@@ -1,41 +1,136 @@
 use crate::database::DBsResults;
 use crate::diff::formatter::{self, FmtOutput};
-use crate::Config;
-use std::fs::{self, File};
+use crate::diff::repair;
+use crate::{Config, Job};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
 use std::io::LineWriter;
 use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Debug, Default)]
 pub enum IOType {
     #[default]
     Stdout,
     File(LineWriter<File>),
+    JsonLines(LineWriter<File>),
+    Repair(LineWriter<File>),
     Test(Vec<String>),
-    Phantom,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    #[value(name = "jsonl")]
+    JsonLines,
+    Repair,
+}
+
+impl Format {
+    pub fn extension(&self) -> &str {
+        match self {
+            Self::Text => "diff",
+            Self::JsonLines => "jsonl",
+            Self::Repair => "sql",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "jsonl" => Ok(Format::JsonLines),
+            "repair" => Ok(Format::Repair),
+            _ => Err(anyhow::anyhow!("Unknown format: {}", s)),
+        }
+    }
+}
+
+/// Job, table and row key the written results belong to.
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub job: Job,
+    pub table: Option<String>,
+    pub key: Vec<String>,
+    /// Target database of the written results, only set when comparing several.
+    pub target: Option<String>,
+    /// Column types by column name, only loaded for the repair format.
+    pub types: HashMap<String, String>,
 }
 
 pub trait IO {
-    fn write(&mut self, config: &Config, result: DBsResults);
+    /// Writes the differences of `result` and returns how many were found.
+    fn write(&mut self, config: &Config, block: &Block, result: DBsResults) -> usize;
     fn echo(&mut self, msg: &str);
     fn close(&mut self);
     // fn new(config: &Args) -> Self;
-    fn new_from_path(file_path: String) -> Self;
+    fn new_from_path(file_path: String, format: Format) -> Self;
+    /// As `new_from_path`, keeping what the file already contains.
+    fn append_from_path(file_path: String, format: Format) -> Self;
     fn is_stdout(&self) -> bool;
     fn start_block(&mut self, msg: &str);
     fn end_block(&mut self, msg: &str);
     fn comment(&mut self, msg: &str);
+    /// Records the results of `header` as unknown because reading them failed with `error`.
+    fn error(&mut self, block: &Block, header: &str, error: &str);
+    /// Records the results of `header` as rows that all matched.
+    fn matched(&mut self, header: &str);
     fn read(&self) -> String;
 }
 
 impl IO for IOType {
-    fn new_from_path(file_path: String) -> Self {
-        Self::File(new_file(&file_path))
+    fn new_from_path(file_path: String, format: Format) -> Self {
+        match format {
+            Format::Text => Self::File(new_file(&file_path, false)),
+            Format::JsonLines => Self::JsonLines(new_file(&file_path, false)),
+            Format::Repair => Self::Repair(new_file(&file_path, false)),
+        }
+    }
+    fn append_from_path(file_path: String, format: Format) -> Self {
+        match format {
+            Format::Text => Self::File(new_file(&file_path, true)),
+            Format::JsonLines => Self::JsonLines(new_file(&file_path, true)),
+            Format::Repair => Self::Repair(new_file(&file_path, true)),
+        }
     }
-    fn write(&mut self, config: &Config, result: DBsResults) {
-        let list = formatter::call(config, result);
+    fn write(&mut self, config: &Config, block: &Block, result: DBsResults) -> usize {
+        if let Self::JsonLines(file) = self {
+            let findings = formatter::findings(&block.key, &result);
+            let count = findings.len();
+            for mut finding in findings {
+                finding.insert("job".to_string(), Value::from(block.job.to_string()));
+                finding.insert("table".to_string(), Value::from(block.table.clone()));
+                if let Some(target) = &block.target {
+                    finding.insert("target".to_string(), Value::from(target.clone()));
+                }
+                finding.insert("header".to_string(), Value::from(result.0.clone()));
+                write_to_file(file, &serde_json::to_string(&finding).unwrap());
+            }
+            return count;
+        }
+        if let Self::Repair(file) = self {
+            let findings = formatter::findings(&block.key, &result);
+            // only rows compared by key over the same range on both sides
+            if !matches!(block.job, Job::ByID | Job::ByIDExcludingReplicaUpdatedAts) {
+                return findings.len();
+            }
+            for finding in &findings {
+                if let Some(statement) = repair::statement(block, finding) {
+                    write_to_file(file, &statement);
+                }
+            }
+            return findings.len();
+        }
+        let list = formatter::call(config, &block.key, result);
 
+        let mut count = 0;
         for fmt in list {
+            count += formatter::count(&fmt);
             let lines = generate_output(fmt);
             match self {
                 Self::File(file) => {
@@ -57,6 +152,7 @@ impl IO for IOType {
                 }
             }
         }
+        count
     }
 
     fn start_block(&mut self, msg: &str) {
@@ -81,19 +177,47 @@ impl IO for IOType {
         self.echo(msg);
     }
 
+    fn error(&mut self, block: &Block, header: &str, error: &str) {
+        if let Self::JsonLines(file) = self {
+            let mut finding = json!({
+                "job": block.job.to_string(),
+                "table": block.table,
+                "header": header,
+                "kind": "error",
+                "error": error,
+            });
+            if let Some(target) = &block.target {
+                finding["target"] = Value::from(target.clone());
+            }
+            write_to_file(file, &finding.to_string());
+            return;
+        }
+        self.comment(&format!("Error: {header} failed: {error}"));
+    }
+
+    fn matched(&mut self, header: &str) {
+        // as the header of the results, which are not findings
+        if !matches!(self, Self::JsonLines(_) | Self::Repair(_)) {
+            self.comment(header);
+        }
+    }
+
     fn echo(&mut self, msg: &str) {
         if msg.is_empty() {
             return;
         }
         match self {
             Self::File(file) => write_to_file(file, msg),
+            // Only findings are written as JSON Lines
+            Self::JsonLines(_) => {}
+            Self::Repair(file) => write_to_file(file, &format!("-- {msg}")),
             Self::Test(test) => test.push(msg.to_string()),
             _ => println!("{}", msg),
         }
     }
 
     fn close(&mut self) {
-        if let Self::File(file) = self {
+        if let Self::File(file) | Self::JsonLines(file) | Self::Repair(file) = self {
             flush_file(file);
         }
     }
@@ -147,12 +271,17 @@ fn flush_file(file: &mut LineWriter<File>) {
 }
 
 // TODO: this should return a Result
-fn new_file(file_path: &String) -> LineWriter<File> {
+fn new_file(file_path: &String, append: bool) -> LineWriter<File> {
     let folder = Path::new(file_path).parent().unwrap();
     fs::create_dir_all(folder).unwrap_or_else(|_| {
         panic!("unable to create folder {folder:?}");
     });
-    let file = File::create(file_path)
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(file_path)
         .unwrap_or_else(|_| panic!("unable to create diff file at {file_path}"));
     LineWriter::new(file)
 }
@@ -1,5 +1,9 @@
 mod formatter;
 mod io;
+mod repair;
 mod summary;
-pub use io::{IOType, IO};
+mod tolerance;
+pub use formatter::approximately_equal;
+pub use io::{Block, Format, IOType, IO};
 pub use summary::Summary;
+pub use tolerance::{column_tolerances, Tolerance};
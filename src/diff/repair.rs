@@ -0,0 +1,208 @@
+use crate::database::{quote_ident, quote_table, JsonMap};
+use crate::diff::Block;
+use serde_json::Value;
+
+/// SQL statement that makes DB2 match DB1 for a finding, `None` when the
+/// finding can not be located by a row key.
+///
+/// Statements are idempotent so the script can be applied more than once:
+/// - `missing` rows are inserted unless a row with the same key exists.
+/// - `extra` rows are deleted by key.
+/// - `changed` rows get the DB1 value of every changed column.
+pub fn statement(block: &Block, finding: &JsonMap) -> Option<String> {
+    let table = block.table.as_ref()?;
+    let key = finding.get("key")?.as_object()?;
+    if key.is_empty() {
+        return None;
+    }
+    let columns = finding
+        .get("columns")
+        .and_then(|columns| columns.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let condition = key
+        .iter()
+        .map(|(column, value)| {
+            format!(
+                "{} = {}",
+                quote_ident(column),
+                literal(value, block.types.get(column))
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" AND ");
+    let table = quote_table(table);
+
+    match finding.get("kind")?.as_str()? {
+        "missing" => {
+            let (names, values): (Vec<String>, Vec<String>) = columns
+                .iter()
+                .map(|(column, change)| {
+                    (
+                        quote_ident(column),
+                        literal(&change["old"], block.types.get(column)),
+                    )
+                })
+                .unzip();
+            Some(format!(
+                "INSERT INTO {table} ({}) SELECT {} WHERE NOT EXISTS (SELECT 1 FROM {table} WHERE {condition});",
+                names.join(", "),
+                values.join(", ")
+            ))
+        }
+        "extra" => Some(format!("DELETE FROM {table} WHERE {condition};")),
+        "changed" if !columns.is_empty() => {
+            let assignments = columns
+                .iter()
+                .map(|(column, change)| {
+                    format!(
+                        "{} = {}",
+                        quote_ident(column),
+                        literal(&change["old"], block.types.get(column))
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            Some(format!(
+                "UPDATE {table} SET {assignments} WHERE {condition};"
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// `value` as a SQL literal, cast to `column_type` when known.
+fn literal(value: &Value, column_type: Option<&String>) -> String {
+    let cast = |text: String| match column_type {
+        Some(column_type) => format!("{}::{column_type}", quote(&text)),
+        None => quote(&text),
+    };
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(true) => "TRUE".to_string(),
+        Value::Bool(false) => "FALSE".to_string(),
+        // as text, so no digit is lost to a float on the way
+        Value::Number(number) => format!(
+            "{}::{}",
+            quote(&number.to_string()),
+            column_type.map_or("numeric", |t| t.as_str())
+        ),
+        Value::String(text) => cast(text.clone()),
+        Value::Array(items) => match column_type.and_then(|t| t.strip_suffix("[]")) {
+            Some(_) if items.is_empty() => cast("{}".to_string()),
+            Some(item_type) => {
+                let item_type = item_type.to_string();
+                let items = items
+                    .iter()
+                    .map(|item| literal(item, Some(&item_type)))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!("ARRAY[{items}]::{}", column_type.unwrap())
+            }
+            None => cast(value.to_string()),
+        },
+        Value::Object(_) => cast(value.to_string()),
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Job;
+    use serde_json::from_str;
+    use std::collections::HashMap;
+
+    fn block() -> Block {
+        Block {
+            job: Job::ByID,
+            table: Some("public.users".to_string()),
+            key: vec!["id".to_string()],
+            target: None,
+            types: HashMap::from([
+                ("id".to_string(), "integer".to_string()),
+                ("name".to_string(), "character varying".to_string()),
+                ("tags".to_string(), "text[]".to_string()),
+            ]),
+        }
+    }
+
+    fn finding(json: &str) -> JsonMap {
+        from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_statement() {
+        let block = block();
+        assert_eq!(
+            statement(
+                &block,
+                &finding(
+                    r#"{"kind":"missing","key":{"id":1},"columns":{"id":{"old":1,"new":null},"name":{"old":"O'Neil","new":null},"tags":{"old":["a"],"new":null}}}"#
+                )
+            ),
+            Some("INSERT INTO \"public\".\"users\" (\"id\", \"name\", \"tags\") SELECT '1'::integer, 'O''Neil'::character varying, ARRAY['a'::text]::text[] WHERE NOT EXISTS (SELECT 1 FROM \"public\".\"users\" WHERE \"id\" = '1'::integer);".to_string())
+        );
+        assert_eq!(
+            statement(
+                &block,
+                &finding(r#"{"kind":"extra","key":{"id":2},"columns":{}}"#)
+            ),
+            Some("DELETE FROM \"public\".\"users\" WHERE \"id\" = '2'::integer;".to_string())
+        );
+        assert_eq!(
+            statement(
+                &block,
+                &finding(
+                    r#"{"kind":"changed","key":{"id":3},"columns":{"name":{"old":null,"new":"b"},"tags":{"old":[],"new":["b"]}}}"#
+                )
+            ),
+            Some(
+                "UPDATE \"public\".\"users\" SET \"name\" = NULL, \"tags\" = '{}'::text[] WHERE \"id\" = '3'::integer;"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_statement_keeps_numeric_digits() {
+        let block = Block {
+            types: HashMap::from([("amount".to_string(), "numeric(30,10)".to_string())]),
+            ..block()
+        };
+        assert_eq!(
+            statement(
+                &block,
+                &finding(
+                    r#"{"kind":"changed","key":{"id":9007199254740993},"columns":{"amount":{"old":"12345678901234567890.0123456789","new":1}}}"#
+                )
+            ),
+            Some(
+                "UPDATE \"public\".\"users\" SET \"amount\" = '12345678901234567890.0123456789'::numeric(30,10) WHERE \"id\" = '9007199254740993'::numeric;"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_statement_without_key() {
+        let block = block();
+        assert_eq!(
+            statement(
+                &block,
+                &finding(r#"{"kind":"changed","old":"a","new":"b"}"#)
+            ),
+            None
+        );
+        assert_eq!(
+            statement(
+                &block,
+                &finding(r#"{"kind":"extra","key":{},"columns":{}}"#)
+            ),
+            None
+        );
+    }
+}
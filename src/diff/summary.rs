@@ -10,12 +10,13 @@ pub struct Summary {
     pub updated: usize,
     pub deleted: usize,
     pub created: usize,
-    pub updated_rows: Vec<u32>,
+    /// Columns matching rows, `id` unless the diff names other key columns.
+    pub key: Vec<String>,
+    /// Key of each updated row, its values joined by `, `.
+    pub updated_rows: Vec<String>,
     pub updated_columns: HashMap<String, usize>,
 }
 
-const ANSI_CHARS: &str = r##"[\\u001B\\u009B][[\\]()#;?]*(?:(?:(?:(?:;[-a-zA-Z\\d\\/#&.:=?%@~_]+)*|[a-zA-Z\\d]+(?:;[-a-zA-Z\\d\\/#&.:=?%@~_]*)*)?\\u0007)|(?:(?:\\d{1,4}(?:;\\d{0,4})*)?[\\dA-PR-TZcf-nq-uy=><~]))"##;
-
 impl Summary {
     fn new() -> Self {
         Self {
@@ -23,6 +24,7 @@ impl Summary {
             updated: 0,
             deleted: 0,
             created: 0,
+            key: vec!["id".to_string()],
             updated_rows: Vec::new(),
             updated_columns: HashMap::new(),
         }
@@ -32,16 +34,17 @@ impl Summary {
         if line.contains("#start#") {
             let table = capture_table(line);
             self.table = table.unwrap();
+        } else if line.starts_with("@@ Key: ") {
+            self.key = capture_key_columns(line);
         } else if line.starts_with("> ") {
             self.updated += 1;
-            if let Some(id) = capture_id(line) {
-                self.updated_rows.push(id);
+            if let Some(key) = capture_key(line, &self.key) {
+                self.updated_rows.push(key);
             }
-            if let Some(column_names) = capture_column_names(line) {
-                for column_name in column_names {
-                    let count = self.updated_columns.entry(column_name).or_insert(0);
-                    *count += 1;
-                }
+        } else if line.starts_with("~ ") {
+            if let Some(column_name) = capture_column_name(line) {
+                let count = self.updated_columns.entry(column_name).or_insert(0);
+                *count += 1;
             }
         } else if line.starts_with("- ") {
             self.deleted += 1;
@@ -73,9 +76,9 @@ impl Summary {
         println!("  Updated: {}", self.updated);
         println!("  Deleted: {}", self.deleted);
         println!("  Created: {}", self.created);
-        println!("  Updated ids:");
+        println!("  Updated keys ({}):", self.key.join(", "));
         for chunk in self.updated_rows.chunks(20) {
-            println!("    - {:?}", chunk);
+            println!("    - [{}]", chunk.join("; "));
         }
         println!("  Updated columns:");
         for (column, count) in &self.updated_columns {
@@ -90,33 +93,33 @@ fn capture_table(line: &str) -> Option<String> {
     caps.name("table").map(|m| m.as_str().to_string())
 }
 
-fn capture_column_names(line: &str) -> Option<Vec<String>> {
-    let json_regex = Regex::new(r#""([^"]+)":([^,]+)"#).unwrap();
-    let mut acc = Vec::new();
-    for captures in json_regex.captures_iter(line) {
-        if let (Some(k), Some(v)) = (captures.get(1), captures.get(2)) {
-            let pair = (k.as_str(), v.as_str());
-            let ansi_re = Regex::new(ANSI_CHARS).unwrap();
-            if ansi_re.is_match(pair.1) {
-                acc.push(pair.0.to_string());
-            }
-        }
-    }
-
-    if !acc.is_empty() {
-        Some(acc)
-    } else {
-        None
-    }
-}
-fn capture_id(line: &str) -> Option<u32> {
-    let re = Regex::new(r###"id":(?P<id>\d+)"###).unwrap();
+fn capture_column_name(line: &str) -> Option<String> {
+    let re = Regex::new(r"^~ (?P<column>[^:]+): ").unwrap();
     let caps = re.captures(line)?;
-    if let Some(data) = caps.name("id") {
-        data.as_str().parse::<u32>().ok()
-    } else {
-        None
-    }
+    caps.name("column").map(|m| m.as_str().to_string())
+}
+fn capture_key_columns(line: &str) -> Vec<String> {
+    let re = Regex::new(r"`(?P<column>[^`]+)`").unwrap();
+    re.captures_iter(line)
+        .map(|caps| caps["column"].to_string())
+        .collect()
+}
+
+/// Values of the `key` columns in the row of `line`, joined by `, `.
+fn capture_key(line: &str, key: &[String]) -> Option<String> {
+    let values = key
+        .iter()
+        .map(|column| {
+            let re = Regex::new(&format!(
+                r#""{}":(?P<value>"(?:[^"\\]|\\.)*"|-?[\d.eE+-]+|true|false|null)"#,
+                regex::escape(column)
+            ))
+            .unwrap();
+            let caps = re.captures(line)?;
+            caps.name("value").map(|m| m.as_str().to_string())
+        })
+        .collect::<Option<Vec<String>>>()?;
+    Some(values.join(", "))
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -149,10 +152,25 @@ mod test {
         );
     }
     #[test]
-    fn test_extract_column_name() {
-        let line = r##"> {"created_at":"2020-05-07T20:52:24","id":40,"name":"John-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I-I[1;40;38;5;118m [0m[1;40;38;5;118mchanged[0m","updated_at":"2020-[9;31m05[0m[1;40;38;5;118m06[0m-[9;31m07T20[0m[1;40;38;5;118m06T20[0m:52:24"}"##;
-        let columns = capture_column_names(line).unwrap();
-        assert_eq!(columns[0], "name".to_string());
-        assert_eq!(columns[1], "updated_at".to_string());
+    fn test_capture_id_beyond_u32() {
+        let line = r##"> {"created_at":"2020-05-07T20:52:24","id":5000000040,"name":"John"}"##;
+        assert_eq!(
+            capture_key(line, &["id".to_string()]),
+            Some("5000000040".to_string())
+        );
+    }
+    #[test]
+    fn test_capture_composite_key() {
+        let key = capture_key_columns("@@ Key: `code`, `region` @@");
+        assert_eq!(key, vec!["code".to_string(), "region".to_string()]);
+        let line = r##"> {"code":"acc-\"5","name":"Account 5","region":2}"##;
+        assert_eq!(capture_key(line, &key), Some(r#""acc-\"5", 2"#.to_string()));
+        assert_eq!(capture_key(line, &["id".to_string()]), None);
+    }
+    #[test]
+    fn test_capture_column_name() {
+        let line = r##"~ updated_at: "2020-05-07T20:52:24" => "2020-05-06T20:52:24""##;
+        assert_eq!(capture_column_name(line), Some("updated_at".to_string()));
+        assert_eq!(capture_column_name("> {}"), None);
     }
 }
@@ -0,0 +1,178 @@
+use crate::database::column_of;
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How far apart two values of a column can be and still be approximately equal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    /// Numbers at most this far apart.
+    Absolute(f64),
+    /// Numbers apart by at most this fraction of the greater magnitude.
+    Relative(f64),
+    /// Timestamps at most this many microseconds apart.
+    Duration(i64),
+}
+
+impl FromStr for Tolerance {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("Invalid tolerance: {}", s);
+        let number = |n: &str| n.trim().parse::<f64>().map_err(|_| invalid());
+        if let Some(percent) = s.strip_suffix('%') {
+            return Ok(Tolerance::Relative(number(percent)? / 100.0));
+        }
+        let units = [("ms", 1_000.0), ("s", 1_000_000.0), ("m", 60_000_000.0)];
+        for (unit, micros) in units {
+            if let Some(duration) = s.strip_suffix(unit) {
+                return Ok(Tolerance::Duration((number(duration)? * micros) as i64));
+            }
+        }
+        Ok(Tolerance::Absolute(number(s)?))
+    }
+}
+
+impl Tolerance {
+    /// Whether `a` and `b` are within the tolerance, values that are not
+    /// numbers or timestamps never are.
+    pub fn within(&self, a: &Value, b: &Value) -> bool {
+        match self {
+            Self::Absolute(epsilon) => match (number(a), number(b)) {
+                (Some(a), Some(b)) => (a - b).abs() <= *epsilon,
+                _ => false,
+            },
+            Self::Relative(epsilon) => match (number(a), number(b)) {
+                (Some(a), Some(b)) => (a - b).abs() <= epsilon * a.abs().max(b.abs()),
+                _ => false,
+            },
+            Self::Duration(micros) => match (timestamp(a), timestamp(b)) {
+                (Some(a), Some(b)) => (a - b)
+                    .num_microseconds()
+                    .is_some_and(|diff| diff.abs() <= *micros),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Tolerance of each column of `table`: `column` and `table.column` entries
+/// first, then `type:name` entries for the columns whose `types` start with `name`.
+pub fn column_tolerances(
+    entries: &[(String, Tolerance)],
+    table: &str,
+    types: &HashMap<String, String>,
+) -> HashMap<String, Tolerance> {
+    let mut tolerances = HashMap::new();
+    for (entry, tolerance) in entries {
+        if entry.starts_with("type:") {
+            continue;
+        }
+        if let Some(column) = column_of(entry, table) {
+            tolerances.entry(column).or_insert(*tolerance);
+        }
+    }
+    for (entry, tolerance) in entries {
+        let Some(name) = entry.strip_prefix("type:") else {
+            continue;
+        };
+        for (column, column_type) in types {
+            if column_type.starts_with(name) {
+                tolerances.entry(column.clone()).or_insert(*tolerance);
+            }
+        }
+    }
+    tolerances
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn timestamp(value: &Value) -> Option<NaiveDateTime> {
+    let s = value.as_str()?;
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tolerance_from_str() {
+        assert_eq!(
+            Tolerance::from_str("0.01").unwrap(),
+            Tolerance::Absolute(0.01)
+        );
+        assert_eq!(
+            Tolerance::from_str("10%").unwrap(),
+            Tolerance::Relative(0.1)
+        );
+        assert_eq!(
+            Tolerance::from_str("5ms").unwrap(),
+            Tolerance::Duration(5_000)
+        );
+        assert_eq!(
+            Tolerance::from_str("2s").unwrap(),
+            Tolerance::Duration(2_000_000)
+        );
+        assert!(Tolerance::from_str("fast").is_err());
+    }
+
+    #[test]
+    fn test_tolerance_within() {
+        assert!(Tolerance::Absolute(0.01).within(&json!(1.004), &json!(1)));
+        assert!(!Tolerance::Absolute(0.01).within(&json!(1.02), &json!(1)));
+        assert!(Tolerance::Absolute(0.01).within(&json!("1.004"), &json!(1)));
+        assert!(Tolerance::Relative(0.1).within(&json!(105), &json!(100)));
+        assert!(!Tolerance::Relative(0.1).within(&json!(1.2), &json!(1)));
+        let five_ms = Tolerance::Duration(5_000);
+        assert!(five_ms.within(
+            &json!("2020-05-07T20:52:24.123"),
+            &json!("2020-05-07T20:52:24.127")
+        ));
+        assert!(!five_ms.within(
+            &json!("2020-05-07T20:52:24.123"),
+            &json!("2020-05-07T20:52:24.13")
+        ));
+        assert!(five_ms.within(
+            &json!("2020-05-07T20:52:24.123+00:00"),
+            &json!("2020-05-07T22:52:24.12+02:00")
+        ));
+        assert!(!five_ms.within(&json!("a"), &json!("a ")));
+    }
+
+    #[test]
+    fn test_column_tolerances() {
+        let entries = vec![
+            ("events.price".to_string(), Tolerance::Absolute(0.1)),
+            ("type:double".to_string(), Tolerance::Relative(0.01)),
+            ("at".to_string(), Tolerance::Duration(1_000)),
+        ];
+        let types = HashMap::from([
+            ("price".to_string(), "double precision".to_string()),
+            ("ratio".to_string(), "double precision".to_string()),
+            ("name".to_string(), "text".to_string()),
+        ]);
+        assert_eq!(
+            column_tolerances(&entries, "public.events", &types),
+            HashMap::from([
+                ("price".to_string(), Tolerance::Absolute(0.1)),
+                ("ratio".to_string(), Tolerance::Relative(0.01)),
+                ("at".to_string(), Tolerance::Duration(1_000)),
+            ])
+        );
+        assert_eq!(
+            column_tolerances(&entries, "public.users", &types).get("price"),
+            Some(&Tolerance::Relative(0.01))
+        );
+    }
+}
@@ -1,6 +1,5 @@
-use super::{utils::compare_table_for_all_columns, Job, Output};
-use crate::database;
-use crate::database::RequestBuilder;
+use super::{par_tables, utils::compare_table_for_all_columns, Job, Output};
+use crate::database::{self, RequestBuilder};
 use crate::Config;
 
 fn job() -> Job {
@@ -8,12 +7,17 @@ fn job() -> Job {
 }
 
 pub fn run(config: &Config) -> Result<(), postgres::Error> {
-    let q = RequestBuilder::new(config).column("id");
-    let tables = database::tables_with_column(q.build_master())?.to_s();
-    for table in tables {
-        let mut output = Output::new(config, job(), Some(table.clone()));
-        compare_table_for_all_columns(&mut output, &table, None)?;
+    let tables = database::primary_keys(RequestBuilder::new(config).build_master())?;
+    par_tables(config, tables, |(table, primary_key)| {
+        let Some(output) = Output::resumable(config, job(), &table) else {
+            return Ok(());
+        };
+        let mut output = output
+            .primary_key(Some(&primary_key))
+            .ignore_columns(&table);
+        compare_table_for_all_columns(&mut output, &table, &primary_key, None)?;
         output.end();
-    }
+        Ok(())
+    })?;
     Ok(())
 }
@@ -1,34 +1,39 @@
-use super::{utils::compare_table_for_all_columns, Job, Output};
-use crate::database::{self, DBResultType, RequestBuilder};
+use super::{par_tables, utils::compare_table_for_all_columns, Job, Output};
+use crate::database::{self, DBResultType, PrimaryKey, RequestBuilder};
 use crate::Config;
+use std::collections::HashSet;
 
 fn job() -> Job {
     Job::ByIDExcludingReplicaUpdatedAts
 }
 
 pub fn run(config: &Config) -> Result<(), postgres::Error> {
-    let id_tables =
-        database::tables_with_column(RequestBuilder::new(config).column("id").build_master())?
-            .to_s();
+    let key_tables = database::primary_keys(RequestBuilder::new(config).build_master())?;
     let updated_at_tables =
         database::tables_with_column(RequestBuilder::new(config).column(column()).build_master())?
             .to_s();
-    let tables = updated_at_tables
+    let tables = key_tables
         .into_iter()
-        .filter(|t| id_tables.contains(t))
-        .collect::<Vec<String>>();
-    for table in tables {
-        let mut output = Output::new(config, job(), Some(table.clone()));
+        .filter(|(t, _)| updated_at_tables.contains(t))
+        .collect::<Vec<(String, PrimaryKey)>>();
+    par_tables(config, tables, |(table, primary_key)| {
+        let Some(output) = Output::resumable(config, job(), &table) else {
+            return Ok(());
+        };
+        let mut output = output
+            .primary_key(Some(&primary_key))
+            .ignore_columns(&table);
 
         output.comment(&format!(
             "Excluding replica updated_ats at cut-off: {}",
             config.tm_cutoff.format("%Y-%m-%d %H:%M:%S")
         ));
-        let ids = updated_ids_after_cutoff(config, &table)?;
+        let keys = updated_keys_after_cutoff(config, &table, &primary_key)?;
 
-        compare_table_for_all_columns(&mut output, &table, Some(ids))?;
+        compare_table_for_all_columns(&mut output, &table, &primary_key, Some(keys))?;
         output.end();
-    }
+        Ok(())
+    })?;
     Ok(())
 }
 
@@ -36,14 +41,24 @@ fn column() -> String {
     "updated_at".to_string()
 }
 
-fn updated_ids_after_cutoff(config: &Config, table: &str) -> Result<Vec<u32>, postgres::Error> {
+fn updated_keys_after_cutoff(
+    config: &Config,
+    table: &str,
+    primary_key: &PrimaryKey,
+) -> Result<HashSet<String>, postgres::Error> {
     let q = RequestBuilder::new(config)
         .table(table)
+        .primary_key(primary_key)
         .tm_cutoff(config.tm_cutoff);
 
-    if let DBResultType::Ids(ids) = database::updated_ids_after_cutoff(q.build_replica())? {
-        Ok(ids)
-    } else {
-        panic!("Expected DBResultType::Ids");
+    // a key updated in any target is left out of the comparison with every target
+    let mut keys = HashSet::new();
+    for target in q.build_targets() {
+        if let DBResultType::Keys(target_keys) = database::updated_keys_after_cutoff(target)? {
+            keys.extend(target_keys);
+        } else {
+            panic!("Expected DBResultType::Keys");
+        }
     }
+    Ok(keys)
 }
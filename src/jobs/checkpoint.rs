@@ -0,0 +1,148 @@
+use crate::database::{JsonMap, Key};
+use crate::{Config, Job};
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::Mutex;
+
+/// Progress of the tables compared by a run, by job and table, saved to
+/// `state.json` in the output folder after every compared window so an
+/// interrupted run can continue with `--resume`.
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    state: Mutex<JsonMap>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableProgress {
+    pub done: bool,
+    /// Upper bound of the next id window.
+    pub upper_bound: Option<i64>,
+    /// Upper key of the next page for tables not keyed by a single integer.
+    pub upper_key: Option<Key>,
+    /// Rows compared so far, counted against `by_id_sample_size`.
+    pub compared: u64,
+    /// Differences found so far.
+    pub findings: usize,
+    /// Seed of the ids sampled by `SampleStrategy::Random`, so a resumed run
+    /// draws the same sample.
+    pub seed: Option<i64>,
+}
+
+impl Checkpoint {
+    pub fn path(config: &Config) -> String {
+        format!("{}/state.json", config.output_folder)
+    }
+
+    /// Continues from the state saved by a previous run, if any.
+    pub fn load(&self, config: &Config) -> anyhow::Result<()> {
+        let path = Self::path(config);
+        if let Ok(data) = fs::read_to_string(&path) {
+            let state = serde_json::from_str(&data)
+                .map_err(|e| anyhow!("unable to parse state file at {path}: {e}"))?;
+            *self.state.lock().unwrap() = state;
+        }
+        Ok(())
+    }
+
+    pub fn table(&self, job: Job, table: &str) -> Option<TableProgress> {
+        let state = self.state.lock().unwrap();
+        let progress = state.get(&job.to_string())?.get(table)?;
+        Some(TableProgress::from_json(progress))
+    }
+
+    pub fn save_table(&self, config: &Config, job: Job, table: &str, progress: &TableProgress) {
+        let mut state = self.state.lock().unwrap();
+        let tables = state
+            .entry(job.to_string())
+            .or_insert_with(|| Value::Object(JsonMap::new()));
+        tables[table] = progress.to_json();
+
+        let path = Self::path(config);
+        let tmp = format!("{path}.tmp");
+        fs::create_dir_all(&config.output_folder)
+            .and_then(|_| fs::write(&tmp, serde_json::to_string_pretty(&*state).unwrap()))
+            .and_then(|_| fs::rename(&tmp, &path))
+            .unwrap_or_else(|e| panic!("unable to write state file at {path}: {e}"));
+    }
+
+    /// Forgets the saved state once every job finished.
+    pub fn clear(&self, config: &Config) {
+        self.state.lock().unwrap().clear();
+        let _ = fs::remove_file(Self::path(config));
+    }
+}
+
+impl TableProgress {
+    fn to_json(&self) -> Value {
+        json!({
+            "done": self.done,
+            "upper_bound": self.upper_bound,
+            "upper_key": self.upper_key,
+            "compared": self.compared,
+            "findings": self.findings,
+            "seed": self.seed,
+        })
+    }
+
+    fn from_json(value: &Value) -> Self {
+        Self {
+            done: value["done"].as_bool().unwrap_or(false),
+            upper_bound: value["upper_bound"].as_i64(),
+            upper_key: value["upper_key"].as_array().cloned(),
+            compared: value["compared"].as_u64().unwrap_or(0),
+            findings: value["findings"].as_u64().unwrap_or(0) as usize,
+            seed: value["seed"].as_i64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let config = Config {
+            output_folder: "tmp/checkpoint_test".to_string(),
+            ..Config::default()
+        };
+        let progress = TableProgress {
+            upper_key: Some(vec![Value::from("acc-5"), Value::from(2)]),
+            compared: 10,
+            findings: 3,
+            ..TableProgress::default()
+        };
+        config
+            .checkpoint
+            .save_table(&config, Job::ByID, "public.accounts", &progress);
+
+        let resumed = Config {
+            output_folder: "tmp/checkpoint_test".to_string(),
+            ..Config::default()
+        };
+        resumed.checkpoint.load(&resumed).unwrap();
+        assert_eq!(
+            resumed.checkpoint.table(Job::ByID, "public.accounts"),
+            Some(progress)
+        );
+        assert_eq!(resumed.checkpoint.table(Job::ByID, "public.users"), None);
+
+        resumed.checkpoint.clear(&resumed);
+        assert!(!std::path::Path::new(&Checkpoint::path(&resumed)).exists());
+    }
+
+    #[test]
+    fn test_load_invalid_state() {
+        let config = Config {
+            output_folder: "tmp/checkpoint_invalid_test".to_string(),
+            ..Config::default()
+        };
+        fs::create_dir_all(&config.output_folder).unwrap();
+        fs::write(Checkpoint::path(&config), "{ not json").unwrap();
+
+        let error = config.checkpoint.load(&config).unwrap_err();
+        assert!(error.to_string().contains("unable to parse state file"));
+        config.checkpoint.clear(&config);
+    }
+}
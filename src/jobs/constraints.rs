@@ -0,0 +1,14 @@
+use super::{par_run, utils::compare_definitions, Job};
+use crate::database::{self, RequestBuilder};
+use crate::Config;
+
+pub fn run(config: &Config) -> Result<(), postgres::Error> {
+    let builder = RequestBuilder::new(config);
+    let constraints = par_run(builder, database::get_constraints)?;
+    compare_definitions(
+        config,
+        Job::Constraints,
+        "indexes and constraints",
+        constraints,
+    )
+}
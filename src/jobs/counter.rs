@@ -1,21 +1,26 @@
-use super::{par_run, Job, Output};
+use super::{par_run, par_tables, Job, Output};
 
 use crate::database::{self, DBResultType, RequestBuilder};
 use crate::Config;
 
 pub fn run(config: &Config) -> Result<(), postgres::Error> {
     let tables = database::all_tables(RequestBuilder::new(config).build_master())?.to_s();
-    let mut output = Output::new(config, Job::Counters, None);
-    for table in tables {
+    let counts = par_tables(config, tables, |table| {
         let builder = RequestBuilder::new(config).table(&table);
-        let (result1, result2) = par_run::<u32>(builder, database::count_for)?;
-
-        let diff_result = (
+        let (result1, results) = par_run::<i64>(builder, database::count_for)?;
+        Ok((table, result1, results))
+    })?;
+    let mut output = Output::new(config, Job::Counters, None);
+    for (table, result1, results) in counts {
+        let targets = results
+            .iter()
+            .map(|result| DBResultType::Strings(vec![format!("{}", result)]))
+            .collect();
+        output.write_table_against(
+            &table,
             format!("`{table}` count"),
-            DBResultType::Strings(vec![format!("{}", result1)]),
-            DBResultType::Strings(vec![format!("{}", result2)]),
-        );
-        output.write(diff_result);
+            (DBResultType::Strings(vec![format!("{}", result1)]), targets),
+        )?;
     }
     output.end();
     Ok(())
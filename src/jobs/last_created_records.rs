@@ -2,7 +2,7 @@ use crate::database::{self, DBResultType, RequestBuilder};
 use crate::jobs::Output;
 use crate::Config;
 
-use super::par_run;
+use super::{par_run, par_tables, utils};
 
 pub fn tables(config: &Config) -> Result<(), postgres::Error> {
     let db1_tables = non_updated_at_tables(config)?;
@@ -15,23 +15,28 @@ pub fn tables(config: &Config) -> Result<(), postgres::Error> {
         crate::Job::CreatedAts,
         Some("tables_with_created_at".to_string()),
     );
-    let result = (
+    output.write_against(
         "Tables with `created_at` column but not `updated_at` difference between DBs".to_string(),
-        DBResultType::Strings(db1_tables),
-        DBResultType::Strings(db2_tables),
-    );
-    output.write(result);
+        (
+            DBResultType::Strings(db1_tables),
+            vec![DBResultType::Strings(db2_tables); config.target_urls().len()],
+        ),
+    )?;
     output.end();
     Ok(())
 }
 
 pub fn all_columns(config: &Config) -> Result<(), postgres::Error> {
     let db1_tables = non_updated_at_tables(config)?;
-    for table in db1_tables {
-        let mut output = Output::new(config, crate::Job::CreatedAts, None);
+    let primary_keys = utils::primary_keys(config)?;
+    par_tables(config, db1_tables, |table| {
+        let mut output = Output::new(config, crate::Job::CreatedAts, Some(table.clone()))
+            .primary_key(primary_keys.get(&table))
+            .ignore_columns(&table);
         compare_rows(&mut output, &table)?;
         output.end();
-    }
+        Ok(())
+    })?;
     Ok(())
 }
 
@@ -63,9 +68,7 @@ fn non_updated_at_tables(config: &Config) -> Result<Vec<String>, postgres::Error
 fn compare_rows(output: &mut Output, table: &str) -> Result<(), postgres::Error> {
     let config = output.config;
     let builder = RequestBuilder::new(config).table(table).column(column());
-    let (records1, records2) = par_run(builder, database::full_row_ordered_by)?;
-
-    let result = (format!("`{table}` all columns"), records1, records2);
-    output.write(result);
+    let records = par_run(builder, database::full_row_ordered_by)?;
+    output.write_against(format!("`{table}` all columns"), records)?;
     Ok(())
 }
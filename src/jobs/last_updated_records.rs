@@ -2,23 +2,18 @@ use super::{Job, Output};
 use crate::database::{self, RequestBuilder};
 use crate::Config;
 
-use super::par_run;
+use super::{par_run, par_tables, utils};
 
 pub fn tables(config: &Config) -> Result<(), postgres::Error> {
     let builder = RequestBuilder::new(config).column(column());
-    let (db1_tables, db2_tables) = par_run(builder, database::tables_with_column)?;
+    let tables = par_run(builder, database::tables_with_column)?;
     let mut output = Output::new(
         config,
         Job::UpdatedAts,
         Some("tables_with_updated_at".to_string()),
     );
 
-    let result = (
-        "Tables with `updated_at` column".to_string(),
-        db1_tables,
-        db2_tables,
-    );
-    output.write(result);
+    output.write_against("Tables with `updated_at` column".to_string(), tables)?;
     output.end();
     Ok(())
 }
@@ -26,11 +21,15 @@ pub fn tables(config: &Config) -> Result<(), postgres::Error> {
 pub fn all_columns(config: &Config) -> Result<(), postgres::Error> {
     let query = RequestBuilder::new(config).column(column());
     let db1_tables = database::tables_with_column(query.build_master())?.to_s();
-    for table in db1_tables {
-        let mut output = Output::new(config, Job::UpdatedAts, Some(table.clone()));
+    let primary_keys = utils::primary_keys(config)?;
+    par_tables(config, db1_tables, |table| {
+        let mut output = Output::new(config, Job::UpdatedAts, Some(table.clone()))
+            .primary_key(primary_keys.get(&table))
+            .ignore_columns(&table);
         compare_rows(&mut output, &table)?;
         output.end();
-    }
+        Ok(())
+    })?;
     Ok(())
 }
 
@@ -41,10 +40,8 @@ fn column() -> String {
 fn compare_rows(output: &mut Output, table: &str) -> Result<(), postgres::Error> {
     let config = output.config;
     let builder = RequestBuilder::new(config).table(table).column(column());
-    let (records1, records2) = par_run(builder, database::full_row_ordered_by)?;
-    let result = (format!("`{table}` all columns"), records1, records2);
-
-    output.write(result);
+    let records = par_run(builder, database::full_row_ordered_by)?;
+    output.write_against(format!("`{table}` all columns"), records)?;
 
     Ok(())
 }
@@ -1,19 +1,28 @@
 mod by_id;
 mod by_id_excluding_replica_updated_ats;
+mod checkpoint;
+mod constraints;
 mod counter;
 mod last_created_records;
 mod last_updated_records;
+mod schema;
 mod sequences;
 mod updated_ats_until;
 mod utils;
-use crate::{database::DBsResults, diff, IO};
-use std::{error, fmt, str::FromStr};
-pub(crate) use utils::par_run;
+use crate::{
+    database::{self, DBResultType, DBsResults, PrimaryKey, RequestBuilder, TargetError},
+    diff, IO,
+};
+pub use checkpoint::Checkpoint;
+use checkpoint::TableProgress;
+use itertools::Itertools;
+use std::{collections::HashMap, error, fmt, str::FromStr};
+pub(crate) use utils::{par_run, par_tables};
 
 use crate::Config;
 use anyhow::Result;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Job {
     Counters,
     UpdatedAts,
@@ -22,6 +31,8 @@ pub enum Job {
     Sequences,
     UpdatedAtsUntil,
     ByIDExcludingReplicaUpdatedAts,
+    Schema,
+    Constraints,
 }
 
 impl fmt::Display for Job {
@@ -36,6 +47,8 @@ impl fmt::Display for Job {
             Self::ByIDExcludingReplicaUpdatedAts => {
                 "by_id_excluding_replica_updated_ats".to_string()
             }
+            Self::Schema => "schema".to_string(),
+            Self::Constraints => "constraints".to_string(),
         };
         write!(f, "{name}")
     }
@@ -54,11 +67,60 @@ impl FromStr for Job {
             "sequences" => Ok(Job::Sequences),
             "updated_ats_until" => Ok(Job::UpdatedAtsUntil),
             "by_id_excluding_replica_updated_ats" => Ok(Job::ByIDExcludingReplicaUpdatedAts),
+            "schema" => Ok(Job::Schema),
+            "constraints" => Ok(Job::Constraints),
             _ => Err(anyhow::anyhow!("Unknown job: {}", s)),
         }
     }
 }
 
+/// How the `by_id` jobs walk tables keyed by a single integer column.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Pagination {
+    /// Windows of `limit` ids down from the greatest id, empty or not.
+    #[default]
+    Windows,
+    /// Windows ending at the `limit`th row present in either database, so
+    /// sparse id ranges take as many queries as they have rows.
+    Keyset,
+}
+
+impl FromStr for Pagination {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "windows" => Ok(Pagination::Windows),
+            "keyset" => Ok(Pagination::Keyset),
+            _ => Err(anyhow::anyhow!("Unknown pagination: {}", s)),
+        }
+    }
+}
+
+/// Which rows of a table keyed by a single integer column the `by_id` jobs
+/// compare when `by_id_sample_size` is set.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SampleStrategy {
+    /// The greatest ids.
+    #[default]
+    Newest,
+    /// Ids picked at random with `TABLESAMPLE`.
+    Random,
+    /// Windows evenly spaced between the least and the greatest id.
+    Spread,
+}
+
+impl FromStr for SampleStrategy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(SampleStrategy::Newest),
+            "random" => Ok(SampleStrategy::Random),
+            "spread" => Ok(SampleStrategy::Spread),
+            _ => Err(anyhow::anyhow!("Unknown sample strategy: {}", s)),
+        }
+    }
+}
+
 impl Job {
     fn run(&self, config: &Config) -> Result<(), Box<dyn error::Error>> {
         match self {
@@ -92,6 +154,14 @@ impl Job {
                 by_id_excluding_replica_updated_ats::run(config)?;
                 Ok(())
             }
+            Self::Schema => {
+                schema::run(config)?;
+                Ok(())
+            }
+            Self::Constraints => {
+                constraints::run(config)?;
+                Ok(())
+            }
         }
     }
 
@@ -112,115 +182,365 @@ impl Job {
         format!("{}/diffs/{self}", config.output_folder)
     }
 
-    pub fn diff_file(&self, config: &Config, table: Option<&String>) -> diff::IOType {
+    /// Diff file of `table`, emptied unless `append`.
+    pub fn diff_file(&self, config: &Config, table: Option<&String>, append: bool) -> diff::IOType {
         let path = format!(
-            "{}/{}.diff",
+            "{}/{}.{}",
             self.diff_folder(config),
-            table.unwrap_or(&"all".to_string())
+            table.unwrap_or(&"all".to_string()),
+            config.format.extension()
         );
 
-        diff::IO::new_from_path(path)
+        if append {
+            diff::IO::append_from_path(path, config.format)
+        } else {
+            diff::IO::new_from_path(path, config.format)
+        }
     }
 }
 
 pub struct Output<'a> {
     config: &'a Config,
-    job: Job,
-    table: Option<String>,
+    block: diff::Block,
+    ignored_columns: Vec<String>,
+    /// Tolerance of each column of the table, resolved on the first write.
+    tolerances: Option<HashMap<String, diff::Tolerance>>,
+    /// The diff file, or in tests a buffer appended to `Config::diff_io` by
+    /// `end` so blocks of tables compared in parallel are not interleaved.
     io: diff::IOType,
+    /// Differences written, including those found before a resume.
+    findings: usize,
+    /// Progress saved to the checkpoint, only for outputs made by `resumable`.
+    progress: Option<TableProgress>,
 }
 
 impl<'a> Output<'a> {
     pub fn new(config: &'a Config, job: Job, table: Option<String>) -> Self {
-        let mut me = if config.test_env {
-            Self {
-                config,
-                job,
-                table,
-                io: diff::IOType::Phantom,
-            }
+        let block = diff::Block {
+            job,
+            table: table.clone(),
+            key: Vec::new(),
+            target: None,
+            types: HashMap::new(),
+        };
+        let io = if config.test_env {
+            diff::IOType::Test(Vec::new())
         } else {
-            Self {
-                config,
-                job,
-                table: table.clone(),
-                io: Self::diff_file(config, job, table),
-            }
+            Self::diff_file(config, job, table)
+        };
+        let mut me = Self {
+            config,
+            block,
+            ignored_columns: Vec::new(),
+            tolerances: None,
+            io,
+            findings: 0,
+            progress: None,
         };
         me.start();
         me
     }
 
-    pub fn write(&mut self, results: DBsResults) {
-        if matches!(self.io, diff::IOType::Phantom) {
-            self.config.diff_io.borrow_mut().write(self.config, results);
-        } else {
-            self.io.write(self.config, results);
+    /// Output of a table whose progress is checkpointed. `None` when a resumed
+    /// run already compared the table, otherwise the output continues the diff
+    /// file of the interrupted run.
+    pub fn resumable(config: &'a Config, job: Job, table: &str) -> Option<Self> {
+        match config.checkpoint.table(job, table) {
+            Some(progress) if progress.done => {
+                config.add_findings(job, progress.findings);
+                None
+            }
+            Some(progress) => {
+                let io = if config.test_env {
+                    diff::IOType::Test(Vec::new())
+                } else {
+                    job.diff_file(config, Some(&table.to_string()), true)
+                };
+                config.add_findings(job, progress.findings);
+                let mut me = Self {
+                    config,
+                    block: diff::Block {
+                        job,
+                        table: Some(table.to_string()),
+                        key: Vec::new(),
+                        target: None,
+                        types: HashMap::new(),
+                    },
+                    ignored_columns: Vec::new(),
+                    tolerances: None,
+                    io,
+                    findings: progress.findings,
+                    progress: Some(progress),
+                };
+                if config.test_env {
+                    // the buffer is not appended to the interrupted block
+                    me.start();
+                }
+                me.comment("Resumed from checkpoint");
+                Some(me)
+            }
+            None => {
+                let mut me = Self::new(config, job, Some(table.to_string()));
+                me.progress = Some(TableProgress::default());
+                Some(me)
+            }
         }
     }
 
-    fn start(&mut self) {
-        if matches!(self.io, diff::IOType::Phantom) {
-            self.config
-                .diff_io
-                .borrow_mut()
-                .echo(&format!("--- {} ---", self.config.db1));
+    /// Where a resumed comparison of the table continues from.
+    pub fn progress(&self) -> TableProgress {
+        self.progress.clone().unwrap_or_default()
+    }
+
+    /// Saves `progress` once everything before it has been written.
+    pub fn checkpoint(&mut self, progress: TableProgress) {
+        if self.progress.is_none() {
+            return;
+        }
+        let progress = TableProgress {
+            findings: self.findings,
+            ..progress
+        };
+        if let Some(table) = &self.block.table {
             self.config
-                .diff_io
-                .borrow_mut()
-                .echo(&format!("+++ {} +++", self.config.db2));
-            let table = format!(
-                "Table: `{}`",
-                self.table.as_ref().unwrap_or(&"all".to_string())
-            );
-            let msg = &format!("Job: `{}` {table}", self.job);
-            self.config.diff_io.borrow_mut().start_block(msg);
+                .checkpoint
+                .save_table(self.config, self.block.job, table, &progress);
+        }
+        self.progress = Some(progress);
+    }
+
+    /// Rows are matched between databases by the columns of `primary_key`.
+    /// Keys other than `id` are named in the output, for `summarize`.
+    pub fn primary_key(mut self, primary_key: Option<&PrimaryKey>) -> Self {
+        self.block.key = primary_key.map(|pk| pk.columns.clone()).unwrap_or_default();
+        if !self.block.key.is_empty() && self.block.key != ["id"] {
+            let columns = self.block.key.iter().map(|c| format!("`{c}`")).join(", ");
+            self.comment(&format!("Key: {columns}"));
+        }
+        self
+    }
+
+    /// Leaves the columns ignored for `table` out of the written rows,
+    /// key columns are kept so rows can still be matched.
+    pub fn ignore_columns(mut self, table: &str) -> Self {
+        self.ignored_columns = self.config.ignored_columns(table);
+        self
+    }
+
+    /// Writes the DB1 and target results of `header` for each target, in
+    /// target order, labelled by target database when there are several.
+    pub fn write_targets(
+        &mut self,
+        header: String,
+        results: Vec<(DBResultType, DBResultType)>,
+    ) -> Result<(), postgres::Error> {
+        let results = results.into_iter().map(|(a, b)| (a, b, 0)).collect();
+        self.write_differing_targets(header, results)
+    }
+
+    /// As `write_targets` for rows of which `matched` pairs found equal were
+    /// dropped, a target whose rows all matched only gets its header.
+    pub fn write_differing_targets(
+        &mut self,
+        header: String,
+        results: Vec<(DBResultType, DBResultType, u64)>,
+    ) -> Result<(), postgres::Error> {
+        let labelled = !self.config.targets.is_empty();
+        for (index, (a, b, matched)) in results.into_iter().enumerate() {
+            let header = if labelled {
+                let target = database::target_name(index);
+                self.block.target = Some(target.clone());
+                format!("{target} {header}")
+            } else {
+                header.clone()
+            };
+            let written = if matched > 0 && a.is_empty() && b.is_empty() {
+                self.io.matched(&header);
+                Ok(())
+            } else {
+                self.write((header, a, b))
+            };
+            if written.is_err() {
+                self.block.target = None;
+                return written;
+            }
+        }
+        self.block.target = None;
+        Ok(())
+    }
+
+    /// As `write_targets`, comparing every target with the same DB1 `reference`.
+    pub fn write_against(
+        &mut self,
+        header: String,
+        (reference, targets): (DBResultType, Vec<DBResultType>),
+    ) -> Result<(), postgres::Error> {
+        let results = targets
+            .into_iter()
+            .map(|target| (reference.clone(), target))
+            .collect();
+        self.write_targets(header, results)
+    }
+
+    /// As `write_against` for results about `table`, in an output covering
+    /// several tables.
+    pub fn write_table_against(
+        &mut self,
+        table: &str,
+        header: String,
+        results: (DBResultType, Vec<DBResultType>),
+    ) -> Result<(), postgres::Error> {
+        let block_table = self.block.table.replace(table.to_string());
+        let written = self.write_against(header, results);
+        self.block.table = block_table;
+        written
+    }
+
+    fn write(&mut self, results: DBsResults) -> Result<(), postgres::Error> {
+        let results = if self.ignored_columns.is_empty() {
+            results
         } else {
-            self.io.echo(&format!("--- {} ---", self.config.db1));
-            self.io.echo(&format!("+++ {} +++", self.config.db2));
-            let table = format!(
-                "Table: `{}`",
-                self.table.as_ref().unwrap_or(&"all".to_string())
-            );
-            let msg = &format!("Job: `{}` {table}", self.job);
-            self.io.start_block(msg);
+            let columns: Vec<String> = self
+                .ignored_columns
+                .iter()
+                .filter(|column| !self.block.key.contains(column))
+                .cloned()
+                .collect();
+            let (header, a, b) = results;
+            (
+                header,
+                a.exclude_columns(&columns),
+                b.exclude_columns(&columns),
+            )
+        };
+        let (results, approximately_equal) = if self.config.tolerances.is_empty() {
+            (results, 0)
+        } else {
+            let tolerances = self.tolerances()?;
+            diff::approximately_equal(&self.block.key, results, &tolerances)
+        };
+        if self.config.format == diff::Format::Repair
+            && !self.block.key.is_empty()
+            && self.block.types.is_empty()
+        {
+            self.load_column_types()?;
         }
+        let findings = self.io.write(self.config, &self.block, results);
+        self.findings += findings;
+        self.config.add_findings(self.block.job, findings);
+        if approximately_equal > 0 {
+            self.config
+                .add_approximately_equal(self.block.job, approximately_equal);
+            self.io
+                .comment(&format!("{approximately_equal} rows approximately equal"));
+        }
+        Ok(())
     }
 
-    pub fn comment(&mut self, msg: &str) {
-        if matches!(self.io, diff::IOType::Phantom) {
-            self.config.diff_io.borrow_mut().comment(msg);
+    /// Tolerances of the columns of the table, loading the column types when
+    /// tolerances are given by type.
+    fn tolerances(&mut self) -> Result<HashMap<String, diff::Tolerance>, postgres::Error> {
+        if let Some(tolerances) = &self.tolerances {
+            return Ok(tolerances.clone());
+        }
+        let Some(table) = self.block.table.clone() else {
+            return Ok(HashMap::new());
+        };
+        let by_type = self
+            .config
+            .tolerances
+            .iter()
+            .any(|(entry, _)| entry.starts_with("type:"));
+        if by_type && self.block.types.is_empty() {
+            self.load_column_types()?;
+        }
+        let tolerances =
+            diff::column_tolerances(&self.config.tolerances, &table, &self.block.types);
+        self.tolerances = Some(tolerances.clone());
+        Ok(tolerances)
+    }
+
+    /// Records the window described by `header` as not compared because of `error`,
+    /// counted as one difference, labelled with the failing target like `write_targets`.
+    pub fn error(&mut self, header: &str, error: &TargetError) {
+        let header = match error.target {
+            Some(index) if !self.config.targets.is_empty() => {
+                let target = database::target_name(index);
+                self.block.target = Some(target.clone());
+                format!("{target} {header}")
+            }
+            _ => header.to_string(),
+        };
+        println!("=> {header} failed: {error}");
+        self.findings += 1;
+        self.config.add_findings(self.block.job, 1);
+        self.io.error(&self.block, &header, &error.to_string());
+        self.block.target = None;
+    }
+
+    /// Column types of the table in DB1, cast to by repair statements and
+    /// matched by `type:` tolerances.
+    fn load_column_types(&mut self) -> Result<(), postgres::Error> {
+        if let Some(table) = &self.block.table {
+            let q = RequestBuilder::new(self.config).table(table);
+            self.block.types = database::column_types(q.build_master())?
+                .into_iter()
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn start(&mut self) {
+        let msg = &self.block_name();
+        self.io.echo(&format!("--- {} ---", self.config.db1));
+        if self.config.targets.is_empty() {
+            self.io.echo(&format!("+++ {} +++", self.config.db2));
         } else {
-            self.io.comment(msg);
+            for (index, url) in self.config.target_urls().iter().enumerate() {
+                self.io
+                    .echo(&format!("+++ {} {url} +++", database::target_name(index)));
+            }
         }
+        self.io.start_block(msg);
+    }
+
+    pub fn comment(&mut self, msg: &str) {
+        self.io.comment(msg);
     }
 
     pub fn end(&mut self) {
+        let msg = &self.block_name();
+        self.io.end_block(msg);
+        self.io.close();
+        if self.progress.is_some() {
+            self.checkpoint(TableProgress {
+                done: true,
+                ..TableProgress::default()
+            });
+        }
+        if let diff::IOType::Test(lines) = &mut self.io {
+            let mut diff_io = self.config.diff_io.lock().unwrap();
+            for line in lines.drain(..) {
+                diff_io.echo(&line);
+            }
+        }
+    }
+
+    fn block_name(&self) -> String {
         let table = format!(
             "Table: `{}`",
-            self.table.as_ref().unwrap_or(&"all".to_string())
+            self.block.table.as_ref().unwrap_or(&"all".to_string())
         );
-        let msg = &format!("Job: `{}` {table}", self.job);
-        if matches!(self.io, diff::IOType::Phantom) {
-            self.config.diff_io.borrow_mut().end_block(msg);
-            self.config.diff_io.borrow_mut().close();
-        } else {
-            self.io.end_block(msg);
-            self.io.close();
-        };
+        format!("Job: `{}` {table}", self.block.job)
     }
 
     #[allow(dead_code)]
     fn echo(&mut self, msg: &str) {
-        if matches!(self.io, diff::IOType::Phantom) {
-            self.config.diff_io.borrow_mut().echo(msg);
-        } else {
-            self.io.echo(msg);
-        }
+        self.io.echo(msg);
     }
 
     fn diff_file(config: &Config, job: Job, table: Option<String>) -> diff::IOType {
-        job.diff_file(config, Some(&table.unwrap_or("all".to_string())))
+        job.diff_file(config, Some(&table.unwrap_or("all".to_string())), false)
     }
 }
 
@@ -230,3 +550,35 @@ pub fn run(config: &Config) -> Result<(), Box<dyn error::Error>> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pagination_from_str() {
+        assert_eq!(
+            Pagination::from_str("windows").unwrap(),
+            Pagination::Windows
+        );
+        assert_eq!(Pagination::from_str("keyset").unwrap(), Pagination::Keyset);
+        assert!(Pagination::from_str("offset").is_err());
+    }
+
+    #[test]
+    fn test_sample_strategy_from_str() {
+        assert_eq!(
+            SampleStrategy::from_str("newest").unwrap(),
+            SampleStrategy::Newest
+        );
+        assert_eq!(
+            SampleStrategy::from_str("random").unwrap(),
+            SampleStrategy::Random
+        );
+        assert_eq!(
+            SampleStrategy::from_str("spread").unwrap(),
+            SampleStrategy::Spread
+        );
+        assert!(SampleStrategy::from_str("oldest").is_err());
+    }
+}
@@ -0,0 +1,9 @@
+use super::{par_run, utils::compare_definitions, Job};
+use crate::database::{self, RequestBuilder};
+use crate::Config;
+
+pub fn run(config: &Config) -> Result<(), postgres::Error> {
+    let builder = RequestBuilder::new(config);
+    let columns = par_run(builder, database::get_columns)?;
+    compare_definitions(config, Job::Schema, "columns", columns)
+}
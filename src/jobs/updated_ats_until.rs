@@ -2,17 +2,21 @@ use crate::database::{self, DBResultType, RequestBuilder};
 use crate::Config;
 use chrono::NaiveDateTime;
 
-use super::{par_run, Job, Output};
+use super::{par_run, par_tables, utils, Job, Output};
 
 pub fn run(config: &Config) -> Result<(), postgres::Error> {
     let query = RequestBuilder::new(config).column(column());
     let db1_tables = database::tables_with_column(query.build_master())?.to_s();
-    for table in db1_tables {
-        let mut output = Output::new(config, Job::UpdatedAtsUntil, Some(table.clone()));
+    let primary_keys = utils::primary_keys(config)?;
+    par_tables(config, db1_tables, |table| {
+        let mut output = Output::new(config, Job::UpdatedAtsUntil, Some(table.clone()))
+            .primary_key(primary_keys.get(&table))
+            .ignore_columns(&table);
 
         compare_table(&mut output, &table)?;
         output.end();
-    }
+        Ok(())
+    })?;
     Ok(())
 }
 
@@ -29,7 +33,7 @@ fn compare_table(output: &mut Output, table: &str) -> Result<(), postgres::Error
     let mut last_date_time: Option<NaiveDateTime> = Some(config.tm_cutoff);
     while last_date_time.is_some() {
         let builder = builder.clone().tm_cutoff(last_date_time.unwrap());
-        let (records1, records2) = par_run(builder, database::full_row_ordered_by_until)?;
+        let (records1, records) = par_run(builder, database::full_row_ordered_by_until)?;
 
         let header = format!(
             "`{table}` compare rows where `{}` is < '{:?}'",
@@ -37,9 +41,8 @@ fn compare_table(output: &mut Output, table: &str) -> Result<(), postgres::Error
             last_date_time.unwrap()
         );
         last_date_time = get_last_date_time(&records1, last_date_time);
-        if !records1.is_empty() && !records2.is_empty() {
-            let result = (header, records1, records2);
-            output.write(result.clone());
+        if !records1.is_empty() && records.iter().all(|records| !records.is_empty()) {
+            output.write_against(header, (records1, records))?;
         }
     }
     Ok(())
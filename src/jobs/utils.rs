@@ -1,56 +1,570 @@
-use super::Output;
-use crate::database::{self, Request, RequestBuilder};
+use super::{checkpoint::TableProgress, Job, Output, Pagination, SampleStrategy};
+use crate::database::{
+    self, fmt_key, DBResultType, Definitions, DifferingRows, Key, PrimaryKey, Request,
+    RequestBuilder, TargetError,
+};
+use crate::Config;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Runs `f` in DB1 and every target at the same time, DB1 is queried once.
 pub fn par_run<T: Send>(
     r: RequestBuilder,
     f: fn(Request) -> Result<T, postgres::Error>,
-) -> Result<(T, T), postgres::Error> {
-    let (result1, result2) = rayon::join(|| f(r.build_master()), || f(r.build_replica()));
+) -> Result<(T, Vec<T>), TargetError> {
+    let (result1, results) = rayon::join(
+        || f(r.build_master()),
+        || {
+            r.build_targets()
+                .into_par_iter()
+                .enumerate()
+                .map(|(index, q)| f(q).map_err(|e| TargetError::new(Some(index), e)))
+                .collect::<Result<Vec<T>, _>>()
+        },
+    );
 
-    Ok((result1?, result2?))
+    Ok((result1?, results?))
+}
+
+/// Runs `f` for every item, `Config::concurrency` items at a time, and
+/// returns the results in the order of `items`.
+pub fn par_tables<I: Send, T: Send>(
+    config: &Config,
+    items: Vec<I>,
+    f: impl Fn(I) -> Result<T, postgres::Error> + Sync + Send,
+) -> Result<Vec<T>, postgres::Error> {
+    match config.workers.get() {
+        Some(workers) if config.concurrency > 1 => {
+            workers.install(|| items.into_par_iter().map(f).collect())
+        }
+        _ => items.into_iter().map(f).collect(),
+    }
+}
+
+/// Runs `f` again while it fails with a transient error, up to `Config::retries`
+/// times, waiting `Config::retry_backoff` milliseconds doubled after every retry.
+pub fn with_retries<T, E: Borrow<postgres::Error> + Display>(
+    config: &Config,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut backoff = config.retry_backoff;
+    for retry in 1..=config.retries {
+        match f() {
+            Err(error) if database::is_transient(error.borrow()) => {
+                println!(
+                    "=> retry {retry}/{} in {backoff}ms after: {error}",
+                    config.retries
+                );
+                thread::sleep(Duration::from_millis(backoff));
+                backoff = backoff.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+    f()
+}
+
+/// Primary key of every table in DB1, by table name.
+pub fn primary_keys(config: &Config) -> Result<HashMap<String, PrimaryKey>, postgres::Error> {
+    let keys = database::primary_keys(RequestBuilder::new(config).build_master())?;
+    Ok(keys.into_iter().collect())
 }
 
 pub fn compare_table_for_all_columns(
     output: &mut Output,
     table: &str,
-    excluding_ids: Option<Vec<u32>>,
+    primary_key: &PrimaryKey,
+    excluding_keys: Option<HashSet<String>>,
 ) -> Result<(), postgres::Error> {
+    let excluding_keys = excluding_keys.map(Arc::new);
+    if !primary_key.is_integer() {
+        return compare_table_by_key(output, table, primary_key, &excluding_keys);
+    }
     let config = output.config;
-    let q = RequestBuilder::new(config).table(table);
-    let mut upper_bound = database::get_greatest_id_from(q.build_master())?;
-    let mut counter = 0u32;
-    while upper_bound != 0 {
-        if config.by_id_sample_size.is_some() && counter >= config.by_id_sample_size.unwrap() {
+    let q = RequestBuilder::new(config)
+        .table(table)
+        .primary_key(primary_key);
+    let checksum = config.checksum && !config.relaxes_values(table);
+    if config.checksum && !checksum {
+        output.comment(&format!(
+            "`{table}` compared without checksums, its values are normalized or have tolerances"
+        ));
+    }
+    if config.by_id_sample_size.is_some() && !checksum {
+        match config.sample_strategy(table) {
+            SampleStrategy::Newest => {}
+            SampleStrategy::Random => {
+                return compare_sampled_ids(output, table, primary_key, &excluding_keys)
+            }
+            SampleStrategy::Spread => {
+                return compare_spread_windows(output, table, primary_key, &excluding_keys)
+            }
+        }
+    }
+    let progress = output.progress();
+    let greatest_id = || with_retries(config, || database::get_greatest_id_from(q.build_master()));
+    if checksum {
+        if config.by_id_sample_size.is_some()
+            && config.sample_strategy(table) != SampleStrategy::Newest
+        {
+            output.comment(&format!(
+                "`{table}` sampled from the greatest ids, sample strategies do not apply with checksums"
+            ));
+        }
+        // the sampled range is the same when resumed, only its top was compared
+        let lower_bound = checksum_lower_bound(config, &q)?;
+        let upper_bound = match progress.upper_bound {
+            Some(upper_bound) => upper_bound,
+            None => greatest_id()?,
+        };
+        if progress.upper_bound.is_some() && upper_bound <= lower_bound {
+            return Ok(());
+        }
+        return compare_id_range_by_checksum(
+            output,
+            table,
+            primary_key,
+            &excluding_keys,
+            (lower_bound, upper_bound),
+        );
+    }
+    let mut upper_bound = match progress.upper_bound {
+        Some(upper_bound) => upper_bound,
+        None => greatest_id()?,
+    };
+    let lower_stop = least_id_bound(config, &q)?;
+    let mut counter = progress.compared;
+    while upper_bound > lower_stop {
+        if config.by_id_sample_size.is_some()
+            && counter >= u64::from(config.by_id_sample_size.unwrap())
+        {
             break;
         }
-        let lower_bound = if upper_bound > config.limit {
-            upper_bound - config.limit
-        } else {
-            0
+        let Some(lower_bound) = window_lower_bound(output, &q, (lower_stop, upper_bound))? else {
+            break;
         };
+        counter += compare_id_range(
+            output,
+            table,
+            primary_key,
+            &excluding_keys,
+            (lower_bound, upper_bound),
+        )?;
+        upper_bound = lower_bound;
+        output.checkpoint(TableProgress {
+            upper_bound: Some(upper_bound),
+            compared: counter,
+            ..TableProgress::default()
+        });
+    }
+    Ok(())
+}
 
-        let builder = RequestBuilder::new(config)
-            .table(table)
-            .bounds((lower_bound, upper_bound));
+/// Lower bound of the ids compared by checksum: the id below the
+/// `by_id_sample_size` rows with the greatest ids of DB1, else below every id
+/// of any database.
+fn checksum_lower_bound(config: &Config, q: &RequestBuilder) -> Result<i64, postgres::Error> {
+    if let Some(size) = config.by_id_sample_size {
+        let lower_bound = with_retries(config, || {
+            database::get_id_after_greatest(q.build_master(), size)
+        })?;
+        if let Some(lower_bound) = lower_bound {
+            return Ok(lower_bound);
+        }
+    }
+    least_id_bound(config, q)
+}
 
-        let (records1, records2) = par_run(builder, database::get_row_by_id_range)?;
-        let (records1, records2) = if excluding_ids.is_some() {
-            let excluding_ids = excluding_ids.as_ref().unwrap();
-            (
-                records1.exclude_ids(excluding_ids),
-                records2.exclude_ids(excluding_ids),
-            )
-        } else {
-            (records1, records2)
+/// Bound below the least id of DB1 and every target, 0 when every id is
+/// positive.
+fn least_id_bound(config: &Config, q: &RequestBuilder) -> Result<i64, postgres::Error> {
+    let (least1, leasts) =
+        with_retries(config, || par_run(q.clone(), database::get_least_id_from))?;
+    let least = leasts.into_iter().fold(least1, i64::min);
+    Ok(if least > 0 {
+        0
+    } else {
+        least.saturating_sub(1)
+    })
+}
+
+/// Lower bound of the window of ids ending at `upper_bound`, not below
+/// `lower_stop`, `None` once a failure was recorded with `--continue-on-error`.
+fn window_lower_bound(
+    output: &mut Output,
+    q: &RequestBuilder,
+    (lower_stop, upper_bound): (i64, i64),
+) -> Result<Option<i64>, postgres::Error> {
+    let config = output.config;
+    match config.pagination {
+        Pagination::Windows => Ok(Some(
+            upper_bound
+                .saturating_sub(i64::from(config.limit))
+                .max(lower_stop),
+        )),
+        Pagination::Keyset => {
+            let builder = q.clone().bounds((lower_stop, upper_bound));
+            match with_retries(config, || par_run(builder.clone(), database::get_id_before)) {
+                // the window ends where any database reaches `limit` rows
+                Ok((lower1, lowers)) => Ok(Some(
+                    lowers
+                        .into_iter()
+                        .fold(lower1, Option::max)
+                        .unwrap_or(lower_stop)
+                        .max(lower_stop),
+                )),
+                Err(error) if config.continue_on_error => {
+                    let table = q.build_master().table.unwrap();
+                    let header =
+                        format!("`{table}` rows with ids up to {upper_bound}, the remaining rows");
+                    output.error(&header, &error);
+                    Ok(None)
+                }
+                Err(error) => Err(error.into()),
+            }
+        }
+    }
+}
+
+/// `SampleStrategy::Random`: compares the rows of `by_id_sample_size` ids
+/// picked uniformly from DB1 with `TABLESAMPLE`, `limit` rows at a time. The
+/// seed of the sample is checkpointed, so a resumed run draws the same ids and
+/// continues below the last compared one.
+fn compare_sampled_ids(
+    output: &mut Output,
+    table: &str,
+    primary_key: &PrimaryKey,
+    excluding_keys: &Option<Arc<HashSet<String>>>,
+) -> Result<(), postgres::Error> {
+    let config = output.config;
+    let size = config.by_id_sample_size.unwrap();
+    let q = RequestBuilder::new(config)
+        .table(table)
+        .primary_key(primary_key)
+        .excluding_keys(excluding_keys);
+    let progress = output.progress();
+    let seed = progress.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| i64::from(elapsed.subsec_nanos()))
+    });
+    let sampled = with_retries(config, || {
+        database::get_sampled_ids(q.build_master(), size, seed)
+    });
+    let ids = match sampled {
+        Ok(ids) => ids,
+        Err(error) if config.continue_on_error => {
+            output.error(&format!("`{table}` sample of {size} ids"), &error.into());
+            return Ok(());
+        }
+        Err(error) => return Err(error),
+    };
+    let ids: Vec<i64> = ids
+        .into_iter()
+        .filter(|id| progress.upper_bound.is_none_or(|resumed| *id < resumed))
+        .collect();
+    for ids in ids.chunks(config.limit as usize) {
+        let header = format!(
+            "`{table}` compare sampled rows with ids from {} to {}",
+            ids.last().unwrap(),
+            ids.first().unwrap()
+        );
+        match with_retries(config, || database::get_rows_by_ids(&q, ids)) {
+            Ok(results) => {
+                write_differing_rows(output, header, results)?;
+            }
+            Err(error) if config.continue_on_error => output.error(&header, &error),
+            Err(error) => return Err(error.into()),
+        }
+        output.checkpoint(TableProgress {
+            upper_bound: ids.last().copied(),
+            seed: Some(seed),
+            ..TableProgress::default()
+        });
+    }
+    Ok(())
+}
+
+/// `SampleStrategy::Spread`: compares windows evenly spaced from the greatest
+/// to the least id of DB1 until `by_id_sample_size` rows were compared.
+fn compare_spread_windows(
+    output: &mut Output,
+    table: &str,
+    primary_key: &PrimaryKey,
+    excluding_keys: &Option<Arc<HashSet<String>>>,
+) -> Result<(), postgres::Error> {
+    let config = output.config;
+    let size = u64::from(config.by_id_sample_size.unwrap());
+    let q = RequestBuilder::new(config)
+        .table(table)
+        .primary_key(primary_key);
+    let greatest = with_retries(config, || database::get_greatest_id_from(q.build_master()))?;
+    let least = with_retries(config, || database::get_least_id_from(q.build_master()))?;
+    let windows = size.div_ceil(u64::from(config.limit)).max(1) as i64;
+    let step = ((greatest - least + 1) / windows).max(i64::from(config.limit));
+    let progress = output.progress();
+    let mut counter = progress.compared;
+    for window in 0..windows {
+        let upper_bound = greatest - window * step;
+        if upper_bound < least || counter >= size {
+            break;
+        }
+        if progress
+            .upper_bound
+            .is_some_and(|resumed| upper_bound >= resumed)
+        {
+            continue;
+        }
+        let bounds = (least.saturating_sub(1), upper_bound);
+        let Some(lower_bound) = window_lower_bound(output, &q, bounds)? else {
+            break;
         };
-        let diff = (
-            format!("`{table}` compare rows with ids from {lower_bound} to {upper_bound}"),
-            records1,
-            records2,
+        // keyset windows of sparse ids never reach into the next window
+        let lower_bound = lower_bound.max(upper_bound - step);
+        counter += compare_id_range(
+            output,
+            table,
+            primary_key,
+            excluding_keys,
+            (lower_bound, upper_bound),
+        )?;
+        output.checkpoint(TableProgress {
+            upper_bound: Some(upper_bound),
+            compared: counter,
+            ..TableProgress::default()
+        });
+    }
+    Ok(())
+}
+
+/// Keyset pagination for keys that are not a single integer column:
+/// DB1 pages `limit` rows at a time and DB2 is read for the same key range.
+fn compare_table_by_key(
+    output: &mut Output,
+    table: &str,
+    primary_key: &PrimaryKey,
+    excluding_keys: &Option<Arc<HashSet<String>>>,
+) -> Result<(), postgres::Error> {
+    let config = output.config;
+    if config.by_id_sample_size.is_some() && config.sample_strategy(table) != SampleStrategy::Newest
+    {
+        output.comment(&format!(
+            "`{table}` sampled from the greatest keys, sample strategies only apply to integer ids"
+        ));
+    }
+    let progress = output.progress();
+    let mut upper_bound: Option<Key> = progress.upper_key;
+    let mut counter = progress.compared;
+    loop {
+        if config.by_id_sample_size.is_some()
+            && counter >= u64::from(config.by_id_sample_size.unwrap())
+        {
+            break;
+        }
+        let builder = RequestBuilder::new(config)
+            .table(table)
+            .primary_key(primary_key)
+            .excluding_keys(excluding_keys)
+            .key_bounds(None, upper_bound.clone());
+        let lower_bound =
+            match with_retries(config, || database::get_key_before(builder.build_master())) {
+                Ok(lower_bound) => lower_bound,
+                Err(error) if config.continue_on_error => {
+                    // the next page starts where this one ends, which is unknown
+                    let header = format!(
+                        "`{table}` rows with keys before {}, the remaining rows",
+                        fmt_key(&upper_bound)
+                    );
+                    output.error(&header, &error.into());
+                    break;
+                }
+                Err(error) => return Err(error),
+            };
+        let builder = builder.key_bounds(lower_bound.clone(), upper_bound.clone());
+        let header = format!(
+            "`{table}` compare rows with keys from {} to {}",
+            fmt_key(&lower_bound),
+            fmt_key(&upper_bound)
         );
-        output.write(diff.clone());
+        match with_retries(config, || database::get_rows_by_key_range(&builder)) {
+            Ok(results) => {
+                counter += write_differing_rows(output, header, results)?;
+            }
+            Err(error) if config.continue_on_error => output.error(&header, &error),
+            Err(error) => return Err(error.into()),
+        }
+        if lower_bound.is_none() {
+            break;
+        }
         upper_bound = lower_bound;
-        counter += config.limit;
+        output.checkpoint(TableProgress {
+            upper_key: upper_bound.clone(),
+            compared: counter,
+            ..TableProgress::default()
+        });
     }
     Ok(())
 }
+
+/// Bisects `(lower_bound, upper_bound]` until the checksums of both databases
+/// match or the range is small enough to compare the full rows. Ranges are
+/// done from the greatest ids down, each checkpointed once done.
+fn compare_id_range_by_checksum(
+    output: &mut Output,
+    table: &str,
+    primary_key: &PrimaryKey,
+    excluding_keys: &Option<Arc<HashSet<String>>>,
+    (lower_bound, upper_bound): (i64, i64),
+) -> Result<(), postgres::Error> {
+    let config = output.config;
+    if upper_bound - lower_bound <= i64::from(config.limit) {
+        compare_id_range(
+            output,
+            table,
+            primary_key,
+            excluding_keys,
+            (lower_bound, upper_bound),
+        )?;
+        checkpoint_id_range(output, lower_bound);
+        return Ok(());
+    }
+    let builder = RequestBuilder::new(config)
+        .table(table)
+        .primary_key(primary_key)
+        .bounds((lower_bound, upper_bound));
+    let checksums = with_retries(config, || {
+        par_run(builder.clone(), database::get_checksum_by_id_range)
+    });
+    let (checksum1, checksums) = match checksums {
+        Ok(checksums) => checksums,
+        Err(error) if config.continue_on_error => {
+            output.error(
+                &format!("`{table}` checksum of ids from {lower_bound} to {upper_bound}"),
+                &error,
+            );
+            checkpoint_id_range(output, lower_bound);
+            return Ok(());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    if checksums.iter().all(|checksum| *checksum == checksum1) {
+        output.comment(&format!(
+            "`{table}` rows with ids from {lower_bound} to {upper_bound} match by checksum"
+        ));
+        checkpoint_id_range(output, lower_bound);
+        return Ok(());
+    }
+    let middle = lower_bound + (upper_bound - lower_bound) / 2;
+    compare_id_range_by_checksum(
+        output,
+        table,
+        primary_key,
+        excluding_keys,
+        (middle, upper_bound),
+    )?;
+    compare_id_range_by_checksum(
+        output,
+        table,
+        primary_key,
+        excluding_keys,
+        (lower_bound, middle),
+    )
+}
+
+/// Saves that ids down to `lower_bound` were compared.
+fn checkpoint_id_range(output: &mut Output, lower_bound: i64) {
+    output.checkpoint(TableProgress {
+        upper_bound: Some(lower_bound),
+        ..TableProgress::default()
+    });
+}
+
+fn compare_id_range(
+    output: &mut Output,
+    table: &str,
+    primary_key: &PrimaryKey,
+    excluding_keys: &Option<Arc<HashSet<String>>>,
+    (lower_bound, upper_bound): (i64, i64),
+) -> Result<u64, postgres::Error> {
+    let builder = RequestBuilder::new(output.config)
+        .table(table)
+        .primary_key(primary_key)
+        .excluding_keys(excluding_keys)
+        .bounds((lower_bound, upper_bound));
+
+    let header = format!("`{table}` compare rows with ids from {lower_bound} to {upper_bound}");
+    let results = match with_retries(output.config, || database::get_row_by_id_range(&builder)) {
+        Ok(results) => results,
+        Err(error) if output.config.continue_on_error => {
+            output.error(&header, &error);
+            return Ok(0);
+        }
+        Err(error) => return Err(error.into()),
+    };
+    write_differing_rows(output, header, results)
+}
+
+/// Writes the rows differing between DB1 and each target and returns the
+/// rows compared, the most of any target.
+fn write_differing_rows(
+    output: &mut Output,
+    header: String,
+    results: Vec<DifferingRows>,
+) -> Result<u64, postgres::Error> {
+    let compared = results.iter().map(|(_, _, compared)| *compared).max();
+    let results = results
+        .into_iter()
+        .map(|(records1, records2, compared)| {
+            // pairs found equal, every other compared row was kept
+            let kept = u64::from(records1.len()) + u64::from(records2.len());
+            (records1, records2, compared.saturating_sub(kept))
+        })
+        .collect();
+    output.write_differing_targets(header, results)?;
+    Ok(compared.unwrap_or(0))
+}
+
+pub fn compare_definitions(
+    config: &Config,
+    job: Job,
+    name: &str,
+    (definitions1, definitions): (Definitions, Vec<Definitions>),
+) -> Result<(), postgres::Error> {
+    let tables = definitions1
+        .iter()
+        .chain(definitions.iter().flatten())
+        .map(|(table, _)| table.clone())
+        .unique()
+        .sorted();
+
+    for table in tables {
+        let mut output = Output::new(config, job, Some(table.clone()));
+        let targets = definitions
+            .iter()
+            .map(|definitions| DBResultType::Strings(vec![table_definitions(definitions, &table)]))
+            .collect();
+        output.write_against(
+            format!("`{table}` {name}"),
+            (
+                DBResultType::Strings(vec![table_definitions(&definitions1, &table)]),
+                targets,
+            ),
+        )?;
+        output.end();
+    }
+    Ok(())
+}
+
+fn table_definitions(definitions: &[(String, String)], table: &str) -> String {
+    definitions
+        .iter()
+        .filter(|(t, _)| t == table)
+        .map(|(_, definition)| format!("{definition}\n"))
+        .collect()
+}
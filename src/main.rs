@@ -1,18 +1,38 @@
 use clap::Parser;
 use db_compare::{Cli, Commands, Config};
-use std::error;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn error::Error>> {
+/// Differences were found by a job that is not a warning job.
+const EXIT_DIFFERENCES: u8 = 1;
+/// The run could not complete.
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
     let args = Cli::parse();
     match &args.command {
         Commands::Run { .. } => {
-            let config = Config::new(&args.command);
-            db_compare::run(&config)?;
+            let config = match Config::new(&args.command) {
+                Ok(config) => config,
+                Err(error) => {
+                    eprintln!("Error: {error}");
+                    return ExitCode::from(EXIT_ERROR);
+                }
+            };
+            if let Err(error) = db_compare::run(&config) {
+                eprintln!("Error: {error}");
+                return ExitCode::from(EXIT_ERROR);
+            }
+            if config.has_differences() {
+                return ExitCode::from(EXIT_DIFFERENCES);
+            }
         }
         Commands::Summarize { file, .. } => {
-            db_compare::run_summary(file)?;
+            if let Err(error) = db_compare::run_summary(file) {
+                eprintln!("Error: {error}");
+                return ExitCode::from(EXIT_ERROR);
+            }
         }
     }
 
-    Ok(())
+    ExitCode::SUCCESS
 }
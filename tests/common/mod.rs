@@ -7,14 +7,16 @@ use db_compare::*;
 use itertools::Itertools;
 use postgres::{Client, Error, NoTls};
 use pretty_assertions::assert_eq;
-use std::cell::RefCell;
 use std::fs;
 use std::ops::Add;
 use std::path::Path;
+use std::sync::Mutex;
 
 pub enum DB {
     A,
     B,
+    /// Third database, only created for runs with `Config::targets`.
+    C,
     Both,
 }
 
@@ -30,13 +32,14 @@ impl DB {
         match self {
             DB::A => "db_compare_test_db1",
             DB::B => "db_compare_test_db2",
+            DB::C => "db_compare_test_db3",
             DB::Both => panic!("Both is not a valid database name"),
         }
     }
     fn host_connect(&self) -> Result<Client, Error> {
         Client::connect(self.host(), NoTls)
     }
-    fn connect(&self) -> Result<Client, Error> {
+    pub fn connect(&self) -> Result<Client, Error> {
         self.not_both().unwrap();
         Client::connect(&self.url(), NoTls)
     }
@@ -113,14 +116,28 @@ pub struct TestRunner {
     config: Config,
     fixture_folder: String,
     runned: bool,
+    setup_sql: Vec<(DB, String)>,
+    after_snapshot_sql: Vec<(DB, String)>,
+    unordered: bool,
 }
 
 impl TestRunner {
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn new(config: &Config) -> Self {
         fs::create_dir_all("tmp").unwrap();
+        // quoted table names may hold characters unfit for a path
+        let tables = config
+            .white_listed_tables
+            .iter()
+            .flatten()
+            .join("_")
+            .replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_");
         let fixture_folder = format!(
             "tests/fixtures/examples/diffs/{}_{}",
-            config.white_listed_tables.clone().unwrap().join("_"),
+            tables,
             config
                 .jobs
                 .clone()
@@ -132,23 +149,69 @@ impl TestRunner {
 
         Self {
             config: Config {
-                diff_io: RefCell::new(IOType::Test(Vec::new())),
+                diff_io: Mutex::new(IOType::Test(Vec::new())),
                 db1: config.db1.clone(),
                 db2: config.db2.clone(),
+                targets: config.targets.clone(),
                 limit: config.limit,
                 white_listed_tables: config.white_listed_tables.clone(),
+                exclude_tables: config.exclude_tables.clone(),
+                schemas: config.schemas.clone(),
+                exclude_schemas: config.exclude_schemas.clone(),
+                ignore_columns: config.ignore_columns.clone(),
+                normalize: config.normalize.clone(),
+                tolerances: config.tolerances.clone(),
                 jobs: config.jobs.clone(),
+                warning_jobs: config.warning_jobs.clone(),
                 by_id_sample_size: config.by_id_sample_size,
+                sample_strategies: config.sample_strategies.clone(),
+                checksum: config.checksum,
+                pagination: config.pagination,
+                concurrency: config.concurrency,
+                retries: config.retries,
+                retry_backoff: config.retry_backoff,
+                statement_timeout: config.statement_timeout,
+                continue_on_error: config.continue_on_error,
+                snapshot: config.snapshot,
+                resume: config.resume,
+                db1_snapshot: config.db1_snapshot.clone(),
+                db2_snapshot: config.db2_snapshot.clone(),
                 tm_cutoff: config.tm_cutoff,
+                db1_tls: config.db1_tls.clone(),
+                db2_tls: config.db2_tls.clone(),
+                format: config.format,
                 test_env: true,
                 output_folder: "tmp/examples_per_file".to_string(),
                 ..Config::default()
             },
             fixture_folder,
             runned: false,
+            setup_sql: Vec::new(),
+            after_snapshot_sql: Vec::new(),
+            unordered: false,
         }
     }
 
+    /// Runs `sql` in `db` after seeding, to introduce differences other than data.
+    pub fn setup_sql(mut self, db: DB, sql: &str) -> Self {
+        self.setup_sql.push((db, sql.to_string()));
+        self
+    }
+
+    /// Runs `sql` in `db` once DB1 and DB2 snapshots are exported for the
+    /// run, so its changes must not appear in the output.
+    pub fn after_snapshot_sql(mut self, db: DB, sql: &str) -> Self {
+        self.after_snapshot_sql.push((db, sql.to_string()));
+        self
+    }
+
+    /// Compares the output blocks regardless of their order, for tables
+    /// compared in parallel.
+    pub fn unordered(mut self) -> Self {
+        self.unordered = true;
+        self
+    }
+
     fn fixture_file(&self, name: &str) -> String {
         let name = name.to_case(Case::Lower).to_case(Case::Snake);
         format!("{}/{}.diff", self.fixture_folder, name)
@@ -168,17 +231,68 @@ impl TestRunner {
         }
         false
     }
-    pub fn run(mut self, name: &str) -> Self {
-        // setup databases
+    /// Creates and seeds the databases and runs the setup SQL, as `run` does
+    /// before comparing them.
+    pub fn setup(&mut self) {
         before_each().unwrap();
+        if !self.config.targets.is_empty() {
+            DB::C.drop().unwrap();
+            DB::C.setup().unwrap();
+        }
         let (users, updated_at) = generate_users(40);
         let (msgs, _) = generate_msgs(40);
         seed_test_data(Some(&users), Some(&msgs));
-
+        for (db, sql) in &self.setup_sql {
+            db.connect().unwrap().batch_execute(sql).unwrap();
+        }
         self.config.tm_cutoff = updated_at.add(Days::new(10));
+    }
+
+    /// Drops the databases made by `setup`.
+    pub fn teardown(&self) {
+        after_each().unwrap();
+        if !self.config.targets.is_empty() {
+            DB::C.drop().unwrap();
+        }
+    }
+
+    /// Compares the databases set up by `setup` writing diff files under
+    /// `folder`, in the configured format, rather than checking a fixture.
+    pub fn run_to_files(&mut self, folder: &str) {
+        let _ = fs::remove_dir_all(folder);
+        self.config.test_env = false;
+        self.config.output_folder = folder.to_string();
+        db_compare::run(&self.config).unwrap();
+    }
+
+    pub fn run(mut self, name: &str) -> Self {
+        self.setup();
         self.config.test_env = true;
 
+        // the exporting transactions must stay open until the run imports them
+        let mut exporters = Vec::new();
+        if !self.after_snapshot_sql.is_empty() {
+            for db in [DB::A, DB::B] {
+                let mut client = db.connect().unwrap();
+                client
+                    .batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ;")
+                    .unwrap();
+                let snapshot_id: String = client
+                    .query_one("SELECT pg_export_snapshot();", &[])
+                    .unwrap()
+                    .get(0);
+                match db {
+                    DB::A => self.config.db1_snapshot = Some(snapshot_id),
+                    _ => self.config.db2_snapshot = Some(snapshot_id),
+                }
+                exporters.push(client);
+            }
+            for (db, sql) in &self.after_snapshot_sql {
+                db.connect().unwrap().batch_execute(sql).unwrap();
+            }
+        }
         db_compare::run(&self.config).unwrap();
+        drop(exporters);
 
         let fixture_file = self.fixture_file(name);
         if self.has_to_regenerate_fixture(name) {
@@ -189,18 +303,21 @@ impl TestRunner {
                 "[TestRunner]: generating fixture: {}",
                 self.fixture_file(name)
             );
-            let content = self.config.diff_io.borrow().read();
+            let content = self.config.diff_io.lock().unwrap().read();
 
             write_fixture(&fixture_file, content);
         }
         // Copy fixture and result to memory
-        let tmp = self.config.diff_io.borrow().read();
+        let tmp = self.config.diff_io.lock().unwrap().read();
         let fixture = std::fs::read_to_string(&fixture_file).unwrap();
-        // Drop databases
-        after_each().unwrap();
+        self.teardown();
         println!("comparing: result with {}", &fixture_file);
         // Assert the current output is the expected output
-        assert_eq!(fixture, tmp);
+        if self.unordered {
+            assert_eq!(sorted_blocks(&fixture), sorted_blocks(&tmp));
+        } else {
+            assert_eq!(fixture, tmp);
+        }
         self.runned = true;
         self
     }
@@ -209,6 +326,21 @@ impl TestRunner {
 use std::fs::File;
 use std::io::prelude::*;
 
+/// Output blocks, each starting at its `--- db1 ---` line, sorted.
+fn sorted_blocks(output: &str) -> Vec<String> {
+    let mut blocks: Vec<String> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with("--- ") || blocks.is_empty() {
+            blocks.push(String::new());
+        }
+        let block = blocks.last_mut().unwrap();
+        block.push_str(line);
+        block.push('\n');
+    }
+    blocks.sort();
+    blocks
+}
+
 fn write_fixture(path: &str, data: String) {
     let mut file = File::create(path).unwrap();
     file.write_all(data.as_bytes()).unwrap();
@@ -252,6 +384,22 @@ pub fn initial_datetime() -> chrono::NaiveDateTime {
     NaiveDateTime::from_timestamp_opt(initial_timestamp(), 0).unwrap()
 }
 
+/// SQL creating an `events` table with one `Event i` row for each `i` from
+/// 1 to `rows` but the `missing` ones, the `changed` ones named `Event changed`.
+/// Ids are the `id` expression of `i`, such as `i` or `5000000000 + i`.
+pub fn events_fixture(id: &str, rows: u32, missing: &[u32], changed: &[u32]) -> String {
+    let list = |values: &[u32]| values.iter().join(", ");
+    format!(
+        "CREATE TABLE events (id BIGINT PRIMARY KEY, name VARCHAR);
+        INSERT INTO events
+        SELECT {id}, CASE WHEN i = ANY(ARRAY[{}]::int[]) THEN 'Event changed' ELSE 'Event ' || i END
+        FROM generate_series(1, {rows}) i
+        WHERE i <> ALL(ARRAY[{}]::int[]);",
+        list(changed),
+        list(missing)
+    )
+}
+
 impl User {
     #[allow(dead_code)]
     pub fn all(db: DB) -> Vec<Self> {
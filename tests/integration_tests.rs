@@ -1,5 +1,5 @@
 mod common;
-use common::{TestRunner, DB};
+use common::{events_fixture, TestRunner, DB};
 use db_compare::{Commands, Job};
 
 use db_compare::*;
@@ -8,29 +8,251 @@ fn default_args() -> Commands {
     Commands::Run {
         db1: Some(DB::A.url()),
         db2: Some(DB::B.url()),
+        targets: None,
         limit: 1,
         no_tls: false,
+        tls: TlsArgs::default(),
+        checksum: false,
+        pagination: None,
+        snapshot: false,
+        resume: false,
+        db1_snapshot: None,
+        db2_snapshot: None,
         by_id_sample_size: None,
+        sample_strategy: None,
         tables: None,
+        exclude_tables: None,
+        schemas: None,
+        exclude_schemas: None,
         config: None,
         jobs: None,
         tm_cutoff: None,
         output_folder: None,
+        format: None,
+        ignore_columns: None,
+        normalize: None,
+        tolerance: None,
+        warning_jobs: None,
+        pool_size: None,
+        concurrency: None,
+        retries: None,
+        retry_backoff: None,
+        statement_timeout: None,
+        continue_on_error: false,
     }
 }
+fn tables(names: &[&str]) -> Option<Vec<TablePattern>> {
+    Some(names.iter().map(|name| name.parse().unwrap()).collect())
+}
 fn default_config(jobs: Vec<Job>) -> Config {
     Config {
         jobs,
-        white_listed_tables: Some(vec!["users".to_string(), "messages".to_string()]),
-        ..Config::new(&default_args())
+        white_listed_tables: tables(&["users", "messages"]),
+        ..Config::new(&default_args()).unwrap()
     }
 }
 #[test]
 fn test_counters() {
     let config = default_config(vec![Job::Counters]);
 
-    TestRunner::new(&config).run("test_counters");
+    let runner = TestRunner::new(&config).run("test_counters");
+    assert!(runner.config().has_differences());
+}
+#[test]
+fn test_counters_as_warnings() {
+    let mut config = default_config(vec![Job::Counters]);
+    config.warning_jobs = vec![Job::Counters];
+
+    let runner = TestRunner::new(&config).run("test_counters");
+    assert!(!runner.config().has_differences());
+    assert!(runner.config().findings.lock().unwrap()[&Job::Counters] > 0);
+}
+#[test]
+fn test_counters_exclude_tables() {
+    let mut config = default_config(vec![Job::Counters]);
+    config.exclude_tables = tables(&["mess*"]).unwrap();
+
+    TestRunner::new(&config).run("counters exclude tables");
+}
+#[test]
+fn test_json_lines() {
+    let mut config = default_config(vec![Job::Counters, Job::ByID]);
+    config.limit = 10;
+    config.format = Format::JsonLines;
+    let mut runner = TestRunner::new(&config);
+    runner.setup();
+    runner.run_to_files("tmp/json_lines");
+    runner.teardown();
+
+    let read = |file: &str| -> Vec<serde_json::Value> {
+        std::fs::read_to_string(format!("tmp/json_lines/diffs/{file}"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+    let counts = read("counters/all.jsonl");
+    assert_eq!(counts.len(), 2);
+    for count in &counts {
+        assert_eq!(count["job"], "counters");
+        assert_eq!(count["kind"], "changed");
+    }
+    let tables: Vec<&str> = counts
+        .iter()
+        .map(|c| c["table"].as_str().unwrap())
+        .collect();
+    assert!(tables.contains(&"public.users"));
+    assert!(tables.contains(&"public.messages"));
+
+    let users = read("by_id/public.users.jsonl");
+    let kinds = |kind: &str| users.iter().filter(|f| f["kind"] == kind).count();
+    assert_eq!(
+        (kinds("changed"), kinds("missing"), kinds("extra")),
+        (8, 13, 2)
+    );
+    let changed = users.iter().find(|f| f["key"]["id"] == 40).unwrap();
+    assert_eq!(changed["job"], "by_id");
+    assert_eq!(changed["table"], "public.users");
+    assert_eq!(changed["kind"], "changed");
+    assert_eq!(
+        changed["header"],
+        "`public.users` compare rows with ids from 31 to 41"
+    );
+}
+
+#[test]
+fn test_json_lines_error_target() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.format = Format::JsonLines;
+    config.continue_on_error = true;
+    config.targets = vec![DB::C.url()];
+    config.white_listed_tables = tables(&["events"]);
+    let failing = "ALTER TABLE events RENAME TO events_data;
+        CREATE VIEW events AS
+        SELECT id, CASE WHEN 1 / (id - 8) = 0 THEN name ELSE name END AS name FROM events_data;";
+    let mut runner = TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i", 12, &[], &[]))
+        .setup_sql(
+            DB::C,
+            &format!("{} {failing}", events_fixture("i", 12, &[], &[])),
+        );
+    runner.setup();
+    runner.run_to_files("tmp/json_lines_error_target");
+    runner.teardown();
+
+    let records: Vec<serde_json::Value> =
+        std::fs::read_to_string("tmp/json_lines_error_target/diffs/by_id/public.events.jsonl")
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+    assert_eq!(records.len(), 1);
+    let error = &records[0];
+    assert_eq!(error["kind"], "error");
+    assert_eq!(error["target"], "DB3");
+    assert_eq!(
+        error["header"],
+        "DB3 `public.events` compare rows with ids from 7 to 12"
+    );
+}
+
+#[test]
+fn test_repair_with_ignored_columns() {
+    let mut args = default_args();
+    if let Commands::Run {
+        format,
+        ignore_columns,
+        jobs,
+        ..
+    } = &mut args
+    {
+        *format = Some(Format::Repair);
+        *ignore_columns = Some("updated_at".to_string());
+        *jobs = Some("by_id".to_string());
+    }
+    let error = Config::new(&args).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("`--format repair` can not be used with `--ignore-columns`"));
+}
+
+#[test]
+fn test_repair() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 10;
+    config.format = Format::Repair;
+    // rows are located by key, so DB2 keeps one row per id
+    let keyed =
+        "ALTER TABLE users ADD PRIMARY KEY (id); ALTER TABLE messages ADD PRIMARY KEY (id);";
+    let dedup = "DELETE FROM users u USING users c WHERE u.id = c.id AND c.name LIKE '% changed' AND u.name NOT LIKE '% changed';
+        DELETE FROM messages m USING messages c WHERE m.id = c.id AND c.txt LIKE '% changed' AND m.txt NOT LIKE '% changed';";
+    let mut runner = TestRunner::new(&config)
+        .setup_sql(DB::A, keyed)
+        .setup_sql(DB::B, &format!("{dedup} {keyed}"));
+    runner.setup();
+    runner.run_to_files("tmp/repair");
+    assert!(runner.config().has_differences());
+
+    let mut db2 = DB::B.connect().unwrap();
+    for table in ["public.users", "public.messages"] {
+        let file = format!("tmp/repair/diffs/by_id/{table}.sql");
+        if let Ok(script) = std::fs::read_to_string(file) {
+            db2.batch_execute(&script).unwrap();
+        }
+    }
+
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 10;
+    let check = TestRunner::new(&config);
+    db_compare::run(check.config()).unwrap();
+    runner.teardown();
+    assert!(!check.config().has_differences());
 }
+
+#[test]
+fn test_repair_keeps_numeric_digits() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 10;
+    config.format = Format::Repair;
+    config.white_listed_tables = tables(&["amounts"]);
+    let create = "CREATE TABLE amounts (id BIGINT PRIMARY KEY, amount NUMERIC(30,10));";
+    let mut runner = TestRunner::new(&config)
+        .setup_sql(
+            DB::A,
+            &format!(
+                "{create} INSERT INTO amounts VALUES
+                (1, 12345678901234567890.0123456789), (2, 0.1000000001), (3, 1);"
+            ),
+        )
+        .setup_sql(
+            DB::B,
+            &format!("{create} INSERT INTO amounts VALUES (1, 12345678901234567890), (3, 1);"),
+        );
+    runner.setup();
+    runner.run_to_files("tmp/repair_numeric");
+    let script =
+        std::fs::read_to_string("tmp/repair_numeric/diffs/by_id/public.amounts.sql").unwrap();
+    let mut db2 = DB::B.connect().unwrap();
+    db2.batch_execute(&script).unwrap();
+    let amounts: Vec<String> = db2
+        .query("SELECT amount::text FROM amounts ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    runner.teardown();
+    assert_eq!(
+        amounts,
+        vec![
+            "12345678901234567890.0123456789",
+            "0.1000000001",
+            "1.0000000000"
+        ]
+    );
+}
+
 #[test]
 fn test_updated_ats() {
     let config = default_config(vec![Job::UpdatedAts]);
@@ -58,6 +280,561 @@ fn test_all_columns_limit_5() {
     TestRunner::new(&config).run("all columns limit 5");
 }
 
+#[test]
+fn test_all_columns_snapshot() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.snapshot = true;
+
+    TestRunner::new(&config)
+        .after_snapshot_sql(
+            DB::A,
+            "INSERT INTO users (id, name, created_at, updated_at) VALUES (100, 'new', NOW(), NOW());
+            UPDATE messages SET txt = 'updated' WHERE id = 40;",
+        )
+        .after_snapshot_sql(
+            DB::B,
+            "UPDATE users SET name = 'updated' WHERE id = 2;
+            DELETE FROM messages WHERE id = 4;",
+        )
+        .run("all columns");
+}
+
+#[test]
+fn test_quoted_table_names() {
+    let mut config = default_config(vec![Job::ByID, Job::Counters]);
+    config.white_listed_tables = tables(&["Order Items", "select", "MixedCase"]);
+    config.limit = 2;
+    let create = |table: &str| {
+        format!(
+            "CREATE TABLE \"{table}\" (id integer PRIMARY KEY, \"Name\" text);
+            INSERT INTO \"{table}\" VALUES (1, 'one'), (2, 'two'), (3, 'three');"
+        )
+    };
+    let tables = ["Order Items", "select", "MixedCase"]
+        .map(create)
+        .join("\n");
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &tables)
+        .setup_sql(
+            DB::B,
+            &format!(
+                "{tables}
+                UPDATE \"Order Items\" SET \"Name\" = 'changed' WHERE id = 2;
+                DELETE FROM \"select\" WHERE id = 1;
+                INSERT INTO \"MixedCase\" VALUES (4, 'four');"
+            ),
+        )
+        .run("quoted table names");
+}
+
+#[test]
+fn test_schema_qualified_table_names() {
+    let mut config = default_config(vec![Job::Counters, Job::ByID]);
+    config.white_listed_tables = tables(&["users", r#""events.2023""#]);
+    config.limit = 100;
+    let tables = r#"CREATE SCHEMA billing;
+        CREATE TABLE billing.users (id integer PRIMARY KEY, plan text);
+        INSERT INTO billing.users VALUES (1, 'free'), (2, 'pro');
+        CREATE TABLE "events.2023" (id integer PRIMARY KEY, kind text);
+        INSERT INTO "events.2023" VALUES (1, 'click'), (2, 'view');"#;
+    TestRunner::new(&config)
+        .setup_sql(DB::A, tables)
+        .setup_sql(
+            DB::B,
+            &format!(
+                r#"{tables}
+                UPDATE billing.users SET plan = 'team' WHERE id = 2;
+                DELETE FROM "events.2023" WHERE id = 1;"#
+            ),
+        )
+        .run("schema qualified table names");
+}
+
+#[test]
+fn test_all_columns_tls() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.db1_tls = Tls {
+        mode: SslMode::Require,
+        ..Tls::default()
+    };
+    config.db2_tls = Tls {
+        mode: SslMode::Disable,
+        ..Tls::default()
+    };
+
+    TestRunner::new(&config).run("all columns");
+}
+
+#[test]
+fn test_tls_unreadable_files() {
+    let mut config = default_config(vec![Job::Counters]);
+    config.db2_tls = Tls {
+        mode: SslMode::Require,
+        cert: Some("tests/fixtures/missing_client.pem".to_string()),
+        key: Some("tests/fixtures/missing_client.key".to_string()),
+        ..Tls::default()
+    };
+    let mut runner = TestRunner::new(&config);
+    runner.setup();
+    let result = db_compare::run(runner.config());
+    runner.teardown();
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("tests/fixtures/missing_client.pem"));
+}
+
+#[test]
+fn test_all_columns_concurrency() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.concurrency = 2;
+
+    TestRunner::new(&config).unordered().run("all columns");
+}
+
+#[test]
+fn test_counters_concurrency() {
+    let mut config = default_config(vec![Job::Counters]);
+    config.concurrency = 2;
+
+    TestRunner::new(&config).run("test_counters");
+}
+
+#[test]
+fn test_all_columns_resume() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.resume = true;
+    let state = r#"{
+        "by_id": {
+            "public.messages": {"done": true, "findings": 2},
+            "public.users": {"done": false, "upper_bound": 20, "compared": 20, "findings": 3}
+        }
+    }"#;
+    std::fs::create_dir_all("tmp/examples_per_file").unwrap();
+    std::fs::write("tmp/examples_per_file/state.json", state).unwrap();
+
+    let runner = TestRunner::new(&config).run("all columns resume");
+    assert!(runner.config().findings.lock().unwrap()[&Job::ByID] >= 5);
+    assert!(!std::path::Path::new("tmp/examples_per_file/state.json").exists());
+}
+
+#[test]
+fn test_all_columns_checksum_resume() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.checksum = true;
+    config.resume = true;
+    let state = r#"{
+        "by_id": {
+            "public.messages": {"done": true, "findings": 2},
+            "public.users": {"done": false, "upper_bound": 20, "findings": 3}
+        }
+    }"#;
+    std::fs::create_dir_all("tmp/examples_per_file").unwrap();
+    std::fs::write("tmp/examples_per_file/state.json", state).unwrap();
+
+    TestRunner::new(&config).run("all columns checksum resume");
+}
+
+#[test]
+fn test_all_columns_sample_random_resume() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.resume = true;
+    config.by_id_sample_size = Some(20);
+    config.sample_strategies = vec![(None, SampleStrategy::Random)];
+    config.white_listed_tables = tables(&["events"]);
+    let state = r#"{
+        "by_id": {
+            "public.events": {"done": false, "upper_bound": 8, "seed": 42, "findings": 1}
+        }
+    }"#;
+    std::fs::create_dir_all("tmp/examples_per_file").unwrap();
+    std::fs::write("tmp/examples_per_file/state.json", state).unwrap();
+
+    let runner = TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i", 12, &[3], &[10]))
+        .run("all columns sample random resume");
+    assert_eq!(runner.config().findings.lock().unwrap()[&Job::ByID], 2);
+}
+
+#[test]
+fn test_resume_invalid_state() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.resume = true;
+    std::fs::create_dir_all("tmp/examples_per_file").unwrap();
+    std::fs::write("tmp/examples_per_file/state.json", "{ not json").unwrap();
+    let mut runner = TestRunner::new(&config);
+    runner.setup();
+    let result = db_compare::run(runner.config());
+    runner.teardown();
+    std::fs::remove_file("tmp/examples_per_file/state.json").unwrap();
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("unable to parse state file"));
+}
+
+#[test]
+fn test_all_columns_continue_on_error() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.white_listed_tables = tables(&["users"]);
+    config.continue_on_error = true;
+    config.retries = 2;
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::B,
+            "ALTER TABLE users RENAME TO users_data;
+            CREATE VIEW users AS
+            SELECT id, CASE WHEN 1 / (id - 13) = 0 THEN name ELSE name || '' END AS name,
+            created_at, updated_at FROM users_data;",
+        )
+        .run("all columns continue on error");
+}
+
+#[test]
+fn test_all_columns_checksum() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.checksum = true;
+    let same_rows = "DELETE FROM users WHERE id <= 20; DELETE FROM messages WHERE id <= 20;";
+    TestRunner::new(&config)
+        .setup_sql(DB::A, same_rows)
+        .setup_sql(DB::B, same_rows)
+        .run("all columns checksum");
+}
+
+#[test]
+fn test_all_columns_checksum_ignore_columns() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.checksum = true;
+    config.ignore_columns = vec!["created_at".to_string()];
+    let same_rows = "DELETE FROM users WHERE id <= 20; DELETE FROM messages WHERE id <= 20;";
+    TestRunner::new(&config)
+        .setup_sql(DB::A, same_rows)
+        .setup_sql(DB::B, same_rows)
+        .setup_sql(
+            DB::B,
+            "UPDATE users SET created_at = created_at + interval '1 day';
+            UPDATE messages SET created_at = created_at + interval '1 day';",
+        )
+        .run("all columns checksum ignore columns");
+}
+
+#[test]
+fn test_all_columns_ignore_columns() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.ignore_columns = vec!["users.updated_at".to_string(), "created_at".to_string()];
+    TestRunner::new(&config).run("all columns ignore columns");
+}
+
+#[test]
+fn test_all_columns_composite_key() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.white_listed_tables = tables(&["accounts"]);
+    let create = "CREATE TABLE accounts (
+        code VARCHAR, region INTEGER, name VARCHAR, PRIMARY KEY (code, region)
+    );";
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::A,
+            &format!(
+                "{create} INSERT INTO accounts
+                SELECT 'acc-' || i, i % 3, 'Account ' || i FROM generate_series(1, 12) i;"
+            ),
+        )
+        .setup_sql(
+            DB::B,
+            &format!(
+                "{create} INSERT INTO accounts
+                SELECT 'acc-' || i, i % 3, 'Account ' || i FROM generate_series(1, 12) i
+                WHERE i % 4 <> 0;
+                UPDATE accounts SET name = name || ' changed' WHERE region = 1;
+                INSERT INTO accounts VALUES ('acc-0', 0, 'Account 0');"
+            ),
+        )
+        .run("all columns composite key");
+}
+
+#[test]
+fn test_all_columns_composite_key_sample_random() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.by_id_sample_size = Some(5);
+    config.sample_strategies = vec![(None, SampleStrategy::Random)];
+    config.white_listed_tables = tables(&["accounts"]);
+    let create = "CREATE TABLE accounts (
+        code VARCHAR, region INTEGER, name VARCHAR, PRIMARY KEY (code, region)
+    );";
+    let rows = "INSERT INTO accounts
+        SELECT 'acc-' || i, i % 3, 'Account ' || i FROM generate_series(1, 12) i;";
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &format!("{create} {rows}"))
+        .setup_sql(
+            DB::B,
+            &format!("{create} {rows} UPDATE accounts SET name = 'changed' WHERE code = 'acc-8';"),
+        )
+        .run("all columns composite key sample random");
+}
+
+#[test]
+fn test_all_columns_user_defined_key() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 2;
+    config.white_listed_tables = tables(&["labels"]);
+    // no primary key, rows are matched by an `id` of an enum type
+    let create = "CREATE TYPE color AS ENUM ('red', 'green', 'blue', 'black');
+        CREATE TABLE labels (id color, name VARCHAR);";
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::A,
+            &format!(
+                "{create} INSERT INTO labels VALUES
+                ('red', 'Red'), ('green', 'Green'), ('blue', 'Blue');"
+            ),
+        )
+        .setup_sql(
+            DB::B,
+            &format!(
+                "{create} INSERT INTO labels VALUES
+                ('red', 'Red'), ('blue', 'Blue changed'), ('black', 'Black');"
+            ),
+        )
+        .run("all columns user defined key");
+}
+
+#[test]
+fn test_all_columns_bigint_ids() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.by_id_sample_size = Some(10);
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("5000000000 + i", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("5000000000 + i", 12, &[9], &[11]))
+        .run("all columns bigint ids");
+}
+
+#[test]
+fn test_all_columns_keyset_sparse_ids() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.pagination = Pagination::Keyset;
+    config.white_listed_tables = tables(&["events"]);
+    // three small ids, then ids past a gap of a billion
+    let id = "CASE WHEN i <= 3 THEN i ELSE 1000000000 + i - 3 END";
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture(id, 11, &[], &[]))
+        .setup_sql(DB::B, &events_fixture(id, 12, &[2, 7], &[9]))
+        .run("all columns keyset sparse ids");
+}
+
+#[test]
+fn test_all_columns_multiple_targets() {
+    let mut config = default_config(vec![Job::ByID, Job::Counters]);
+    config.limit = 5;
+    config.targets = vec![DB::C.url()];
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i", 12, &[9], &[11]))
+        .setup_sql(DB::C, &events_fixture("i", 13, &[], &[3]))
+        .run("all columns multiple targets");
+}
+
+#[test]
+fn test_all_columns_normalize() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 10;
+    config.normalize = vec![
+        ("at".to_string(), Normalizer::Timestamp(3)),
+        ("events.name".to_string(), Normalizer::Trim),
+        ("events.name".to_string(), Normalizer::Lowercase),
+        ("amount".to_string(), Normalizer::Numeric(None)),
+        ("payload".to_string(), Normalizer::Json),
+    ];
+    config.white_listed_tables = tables(&["events"]);
+    let create = "CREATE TABLE events (
+        id INTEGER PRIMARY KEY, name VARCHAR, at TIMESTAMP, amount NUMERIC, payload TEXT
+    );";
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::A,
+            &format!(
+                "{create} INSERT INTO events VALUES
+                (1, 'Event 1', '2020-05-07 20:52:24.123456', 1.0, '{{\"a\": 1, \"b\": 2}}'),
+                (2, 'Event 2', '2020-05-07 20:52:24.5', 2, '{{\"a\": 1}}'),
+                (3, 'Event 3', '2020-05-07 20:52:24', 3, '[]');"
+            ),
+        )
+        .setup_sql(
+            DB::B,
+            &format!(
+                "{create} INSERT INTO events VALUES
+                (1, 'EVENT 1  ', '2020-05-07 20:52:24.123', 1, '{{\"b\":2,\"a\":1}}'),
+                (2, 'Event 2', '2020-05-07 20:52:24.500', 2.00, '{{\"a\": 2}}'),
+                (3, 'Event 3', '2020-05-07 20:52:24.001', 3, '[]');"
+            ),
+        )
+        .run("all columns normalize");
+}
+
+#[test]
+fn test_all_columns_tolerance() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 10;
+    config.tolerances = vec![
+        ("type:double".to_string(), Tolerance::Absolute(0.01)),
+        ("events.at".to_string(), Tolerance::Duration(5_000)),
+    ];
+    // rows within tolerance differ byte for byte, so checksums are not used
+    config.checksum = true;
+    config.white_listed_tables = tables(&["events"]);
+    let create = "CREATE TABLE events (
+        id INTEGER PRIMARY KEY, name VARCHAR, at TIMESTAMP, amount DOUBLE PRECISION
+    );";
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::A,
+            &format!(
+                "{create} INSERT INTO events VALUES
+                (1, 'Event 1', '2020-05-07 20:52:24.123', 1.0),
+                (2, 'Event 2', '2020-05-07 20:52:24.5', 2.0),
+                (3, 'Event 3', '2020-05-07 20:52:24', 3.0),
+                (4, 'Event 4', '2020-05-07 20:52:24', 4.0);"
+            ),
+        )
+        .setup_sql(
+            DB::B,
+            &format!(
+                "{create} INSERT INTO events VALUES
+                (1, 'Event 1', '2020-05-07 20:52:24.126', 1.004),
+                (2, 'Event 2', '2020-05-07 20:52:24.51', 2.0),
+                (3, 'Event 3', '2020-05-07 20:52:24', 3.5),
+                (4, 'Event 4 changed', '2020-05-07 20:52:24.001', 4.001);"
+            ),
+        )
+        .run("all columns tolerance");
+}
+
+#[test]
+fn test_all_columns_sample_counts_rows() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.by_id_sample_size = Some(6);
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i * 2", 20, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i * 2", 20, &[17], &[14]))
+        .run("all columns sample counts rows");
+}
+
+#[test]
+fn test_all_columns_sample_random() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    // every row is sampled so the output does not depend on the draw
+    config.by_id_sample_size = Some(20);
+    config.sample_strategies = vec![(Some("events".parse().unwrap()), SampleStrategy::Random)];
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i", 12, &[3], &[10]))
+        .run("all columns sample random");
+}
+
+#[test]
+fn test_all_columns_sample_spread() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.by_id_sample_size = Some(15);
+    config.sample_strategies = vec![(None, SampleStrategy::Spread)];
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i", 100, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i", 100, &[31, 50], &[64, 80]))
+        .run("all columns sample spread");
+}
+
+#[test]
+fn test_all_columns_checksum_sample_counts_rows() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.checksum = true;
+    config.by_id_sample_size = Some(6);
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i * 10", 20, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i * 10", 20, &[16], &[19]))
+        .run("all columns checksum sample counts rows");
+}
+
+#[test]
+fn test_all_columns_checksum_negative_ids() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.checksum = true;
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i - 10", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i - 10", 12, &[3], &[11]))
+        .run("all columns checksum negative ids");
+}
+
+#[test]
+fn test_all_columns_negative_ids() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i - 10", 12, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i - 10", 12, &[3], &[11]))
+        .run("all columns negative ids");
+}
+
+#[test]
+fn test_all_columns_keyset_negative_ids() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.pagination = Pagination::Keyset;
+    config.white_listed_tables = tables(&["events"]);
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i - 10", 12, &[], &[]))
+        .setup_sql(
+            DB::B,
+            &format!(
+                "{} INSERT INTO events VALUES (-20, 'Event extra');",
+                events_fixture("i - 10", 12, &[3], &[11])
+            ),
+        )
+        .run("all columns keyset negative ids");
+}
+
+#[test]
+fn test_all_columns_schemas() {
+    let mut config = default_config(vec![Job::ByID]);
+    config.limit = 5;
+    config.white_listed_tables = tables(&["events"]);
+    config.schemas = Some(vec!["billing".to_string()]);
+    let billing = "CREATE SCHEMA billing; SET search_path TO billing;";
+    TestRunner::new(&config)
+        .setup_sql(DB::A, &events_fixture("i", 6, &[], &[]))
+        .setup_sql(DB::B, &events_fixture("i", 6, &[2], &[4]))
+        .setup_sql(
+            DB::A,
+            &format!("{billing} {}", events_fixture("i", 6, &[], &[])),
+        )
+        .setup_sql(
+            DB::B,
+            &format!("{billing} {}", events_fixture("i", 6, &[3], &[5])),
+        )
+        .run("all columns schemas");
+}
+
 #[test]
 fn test_sequences() {
     let config = default_config(vec![Job::Sequences]);
@@ -94,3 +871,32 @@ fn test_all_columns_excluding_replica_updated_ats() {
 
     TestRunner::new(&config).run("all_columns_excluding_replica_updated_ats");
 }
+
+#[test]
+fn test_schema() {
+    let config = default_config(vec![Job::Schema]);
+
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::B,
+            "ALTER TABLE users ADD COLUMN email VARCHAR(100);
+            ALTER TABLE users ALTER COLUMN id TYPE BIGINT;
+            ALTER TABLE messages ALTER COLUMN txt DROP NOT NULL;
+            ALTER TABLE messages ALTER COLUMN created_at SET DEFAULT now();",
+        )
+        .run("schema");
+}
+
+#[test]
+fn test_constraints() {
+    let config = default_config(vec![Job::Constraints]);
+
+    TestRunner::new(&config)
+        .setup_sql(
+            DB::A,
+            "CREATE INDEX messages_created_at_idx ON messages (created_at);
+            ALTER TABLE users ADD CONSTRAINT users_name_not_empty CHECK (name <> '');",
+        )
+        .setup_sql(DB::B, "CREATE INDEX users_name_idx ON users (name);")
+        .run("constraints");
+}